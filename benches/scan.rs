@@ -0,0 +1,38 @@
+//! Parsing and target extraction over a generated vault. The vault is
+//! written to the system's temporary directory once per size.
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use darkstone::{QueryCache, Vault};
+use std::sync::Arc;
+
+#[path = "../tests/support/mod.rs"]
+mod support;
+
+const SIZES: [usize; 2] = [100, 1000];
+
+fn scan(c: &mut Criterion) {
+    let query_cache = Arc::new(QueryCache::new().unwrap());
+    let mut group = c.benchmark_group("scan");
+    group.sample_size(10);
+
+    for notes in SIZES {
+        let dir = std::env::temp_dir().join(format!("darkstone-bench-{}", notes));
+        let root = support::generate(&dir, notes).unwrap();
+
+        group.bench_with_input(BenchmarkId::new("parse", notes), &root, |b, root| {
+            b.iter(|| Vault::with_query_cache(root.clone(), query_cache.clone()).unwrap())
+        });
+        group.bench_with_input(BenchmarkId::new("targets", notes), &root, |b, root| {
+            b.iter(|| {
+                Vault::with_query_cache(root.clone(), query_cache.clone())
+                    .unwrap()
+                    .targets()
+            })
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, scan);
+criterion_main!(benches);
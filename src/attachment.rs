@@ -0,0 +1,21 @@
+use crate::Link;
+use std::path::{Path, PathBuf};
+
+/// A link to a file in the vault that isn't a note, such as an embedded
+/// image or a linked PDF.
+#[derive(Clone, Debug)]
+pub struct Attachment {
+    pub link: Link,
+    /// Where the file was found, or `None` if it doesn't exist.
+    pub path: Option<PathBuf>,
+}
+
+impl Attachment {
+    pub fn is_missing(&self) -> bool {
+        self.path.is_none()
+    }
+
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+}
@@ -0,0 +1,138 @@
+//! An on-disk record of what was extracted from each note, so that notes
+//! which haven't changed since the last scan don't need parsing again.
+
+use crate::{Note, NoteSummary, ScanOptions};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+/// Version of what gets extracted from a note and how a `NoteSummary` is
+/// laid out. Bump it whenever either changes, such as when links are found
+/// in new places or `Link` gains a field, so caches written before the change
+/// are thrown away even by builds that share a package version.
+const FORMAT: u32 = 7;
+
+#[derive(Serialize, Deserialize)]
+struct Entry {
+    stamp: Stamp,
+    note: NoteSummary,
+}
+
+/// Size and modification time, which together decide whether a file has
+/// changed since it was cached.
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+struct Stamp {
+    size: u64,
+    seconds: u64,
+    nanoseconds: u32,
+}
+
+impl Stamp {
+    fn of(path: &Path) -> Option<Self> {
+        let metadata = std::fs::metadata(path).ok()?;
+        let modified = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+
+        Some(Self {
+            size: metadata.len(),
+            seconds: modified.as_secs(),
+            nanoseconds: modified.subsec_nanos(),
+        })
+    }
+}
+
+/// The cache for one vault, with notes keyed by their path relative to the
+/// vault root.
+#[derive(Default, Serialize, Deserialize)]
+pub(crate) struct Cache {
+    /// Caches written by another version of darkstone may have been
+    /// extracted differently, so they are thrown away.
+    version: String,
+    /// `FORMAT` when the cache was written.
+    format: u32,
+    lossy: bool,
+    comments: bool,
+    /// `QueryCache::fingerprint` of the queries the notes were parsed with.
+    queries: u64,
+    notes: HashMap<PathBuf, Entry>,
+}
+
+impl Cache {
+    /// The vault's cache from `dir`, or an empty one if there is none or it
+    /// was made with options that extract notes differently.
+    pub(crate) fn load(dir: &Path, root: &Path, options: &ScanOptions, queries: u64) -> Self {
+        let cache = std::fs::read(Self::file(dir, root))
+            .ok()
+            .and_then(|bytes| serde_json::from_slice::<Cache>(&bytes).ok());
+
+        match cache {
+            Some(cache)
+                if cache.version == env!("CARGO_PKG_VERSION")
+                    && cache.format == FORMAT
+                    && cache.lossy == options.lossy
+                    && cache.comments == options.include_comments
+                    && cache.queries == queries =>
+            {
+                cache
+            }
+            _ => Cache::default(),
+        }
+    }
+
+    /// What was cached for `path`, if the file hasn't changed since.
+    pub(crate) fn get(&self, root: &Path, path: &Path) -> Option<NoteSummary> {
+        let relative = path.strip_prefix(root).unwrap_or(path);
+        let entry = self.notes.get(relative)?;
+
+        match Stamp::of(path) == Some(entry.stamp) {
+            true => Some(entry.note.clone()),
+            false => None,
+        }
+    }
+
+    /// Replaces the vault's cache in `dir` with one holding `notes`.
+    pub(crate) fn save(
+        dir: &Path,
+        root: &Path,
+        options: &ScanOptions,
+        queries: u64,
+        notes: &[Note],
+    ) -> io::Result<()> {
+        let notes = notes
+            .par_iter()
+            .filter_map(|n| {
+                let relative = n.path().strip_prefix(root).unwrap_or(n.path());
+                let entry = Entry {
+                    stamp: Stamp::of(n.path())?,
+                    note: n.summary(),
+                };
+
+                Some((relative.to_path_buf(), entry))
+            })
+            .collect();
+
+        let cache = Cache {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            format: FORMAT,
+            lossy: options.lossy,
+            comments: options.include_comments,
+            queries,
+            notes,
+        };
+
+        std::fs::create_dir_all(dir)?;
+        std::fs::write(Self::file(dir, root), serde_json::to_vec(&cache)?)
+    }
+
+    /// Each vault gets its own file, named after its canonical root.
+    pub(crate) fn file(dir: &Path, root: &Path) -> PathBuf {
+        let root = std::fs::canonicalize(root).unwrap_or(root.to_path_buf());
+        let mut hasher = DefaultHasher::new();
+        root.hash(&mut hasher);
+
+        dir.join(format!("{:016x}.json", hasher.finish()))
+    }
+}
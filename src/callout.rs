@@ -0,0 +1,10 @@
+/// An Obsidian callout: a block quote opening with `> [!type] Title`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Callout {
+    /// The type between `[!` and `]`, lowercased, e.g. `note` or `warning`.
+    pub kind: String,
+    /// Text after the type on the first line, if there is any.
+    pub title: Option<String>,
+    /// Zero-based line the callout starts on.
+    pub line: usize,
+}
@@ -0,0 +1,39 @@
+use crate::{DarkstoneError, Position};
+use tree_sitter::{Language, Query};
+
+/// The trees a note is parsed into, each with its own node types.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Grammar {
+    /// Block structure: headings, lists, code blocks and paragraphs.
+    Markdown,
+    /// The text inside blocks: links, emphasis and code spans.
+    Inline,
+    /// YAML frontmatter between `---` lines.
+    Yaml,
+}
+
+impl Grammar {
+    pub fn language(&self) -> Language {
+        match self {
+            Grammar::Markdown => tree_sitter_md::language(),
+            Grammar::Inline => tree_sitter_md::inline_language(),
+            Grammar::Yaml => tree_sitter_yaml::language(),
+        }
+    }
+
+    /// Compiles `source` into a query over this grammar's trees.
+    pub fn query(&self, source: &str) -> Result<Query, DarkstoneError> {
+        Ok(Query::new(&self.language(), source)?)
+    }
+}
+
+/// A node captured by a query run with `Note::captures`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Capture {
+    /// The capture's name in the query, without the `@`.
+    pub name: String,
+    pub text: String,
+    pub start: Position,
+    /// Where the node ends, exclusive.
+    pub end: Position,
+}
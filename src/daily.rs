@@ -0,0 +1,50 @@
+use chrono::NaiveDate;
+
+/// Obsidian's default daily note name, `2024-05-31`.
+pub const DAILY_FORMAT: &str = "%Y-%m-%d";
+
+/// A window of dates for daily notes, the notes named after the day they
+/// are for.
+#[derive(Clone, Debug)]
+pub struct DateRange {
+    /// The `chrono` format daily note names follow.
+    pub format: String,
+    /// First day in the range, if it has one.
+    pub since: Option<NaiveDate>,
+    /// Last day in the range, if it has one.
+    pub until: Option<NaiveDate>,
+    /// Leave out every note that isn't a daily note, rather than only daily
+    /// notes from outside the range.
+    pub only_daily: bool,
+}
+
+impl DateRange {
+    /// Whether a note called `name` falls in the range.
+    pub fn contains(&self, name: &str) -> bool {
+        match daily_date(name, &self.format) {
+            Some(date) => {
+                self.since.is_none_or(|since| date >= since)
+                    && self.until.is_none_or(|until| date <= until)
+            }
+            None => !self.only_daily,
+        }
+    }
+}
+
+impl Default for DateRange {
+    /// Every note, with daily notes named in `DAILY_FORMAT`.
+    fn default() -> Self {
+        Self {
+            format: DAILY_FORMAT.to_string(),
+            since: None,
+            until: None,
+            only_daily: false,
+        }
+    }
+}
+
+/// The date a note called `name` is for, if `name` is that date written in
+/// `format` and nothing else.
+pub(crate) fn daily_date(name: &str, format: &str) -> Option<NaiveDate> {
+    NaiveDate::parse_from_str(name, format).ok()
+}
@@ -0,0 +1,75 @@
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+/// Everything that can go wrong while loading a vault or one of its notes.
+#[derive(Debug, Error)]
+pub enum DarkstoneError {
+    #[error("{}: {1}", .0.display())]
+    Io(PathBuf, #[source] std::io::Error),
+    #[error("{}: invalid UTF-8: {1}", .0.display())]
+    Utf8(PathBuf, #[source] std::string::FromUtf8Error),
+    #[error("{}: could not be parsed", .0.display())]
+    Parse(PathBuf),
+    /// The vault directory itself could not be walked, e.g. a symlink loop.
+    #[error(transparent)]
+    Walk(#[from] ignore::Error),
+    #[error("invalid glob: {0}")]
+    Glob(#[from] glob::PatternError),
+    /// A grammar could not be loaded, usually because its ABI version doesn't
+    /// match the tree-sitter library.
+    #[error("could not load grammar: {0}")]
+    Language(#[from] tree_sitter::LanguageError),
+    /// One of the built-in queries no longer compiles against its grammar.
+    #[error("invalid query: {0}")]
+    Query(#[from] tree_sitter::QueryError),
+    /// A query from the query directory doesn't compile.
+    #[error("{}: invalid {0} query: {2}", .1.display())]
+    InvalidQuery(String, PathBuf, #[source] tree_sitter::QueryError),
+    /// A query from the query directory lacks a capture the notes rely on.
+    #[error("{}: {0} query has no @{2} capture", .1.display())]
+    MissingCapture(String, PathBuf, String),
+    #[error("vault path '{}' does not exist", .0.display())]
+    NoSuchVault(PathBuf),
+    /// A vault path naming a file, such as a note, rather than a folder.
+    #[error(
+        "vault path '{}' is not a directory; did you mean '{}'?",
+        .0.display(),
+        folder_of(.0).display()
+    )]
+    NotADirectory(PathBuf),
+    #[error("no note named `{0}`")]
+    NoSuchNote(String),
+    /// A new note name that would put the note in another folder or is
+    /// otherwise unusable as a file name.
+    #[error("invalid note name `{0}`")]
+    InvalidName(String),
+    #[error("{}: already exists", .0.display())]
+    Exists(PathBuf),
+    /// A file was edited between planning a change to it and making it.
+    #[error("{}: changed since the vault was scanned", .0.display())]
+    Changed(PathBuf),
+}
+
+impl DarkstoneError {
+    /// The file the error concerns, if it concerns a single file.
+    pub fn path(&self) -> Option<&Path> {
+        match self {
+            DarkstoneError::Io(path, _)
+            | DarkstoneError::Utf8(path, _)
+            | DarkstoneError::Parse(path)
+            | DarkstoneError::InvalidQuery(_, path, _)
+            | DarkstoneError::MissingCapture(_, path, _)
+            | DarkstoneError::Exists(path)
+            | DarkstoneError::Changed(path) => Some(path),
+            _ => None,
+        }
+    }
+}
+
+/// The folder `path` is in, `.` for a bare file name.
+fn folder_of(path: &Path) -> &Path {
+    match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    }
+}
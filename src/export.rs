@@ -0,0 +1,430 @@
+//! Renderers for exporting a vault's link graph and contents to other tools.
+
+use crate::{Vault, VaultIndex};
+use itertools::Itertools;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// The link graph as a Graphviz `digraph`, with one node per note and one
+/// edge per linked pair of notes.
+///
+/// Unresolved links are left out unless `include_broken` is set, when each
+/// unresolved destination becomes a dashed red node, labelled with the
+/// destination and with an id from `broken_id`, and gets a dashed red edge
+/// from each note referring to it.
+pub fn dot(vault: &Vault, include_broken: bool) -> String {
+    let index = vault.index();
+    let mut dot = String::from("digraph {\n");
+
+    let names = vault
+        .notes()
+        .iter()
+        .map(|n| n.name())
+        .sorted()
+        .collect::<Vec<String>>();
+    for name in &names {
+        dot.push_str(&format!("    {};\n", dot_id(name)));
+    }
+
+    let edges = index
+        .edges()
+        .iter()
+        .map(|(source, destination)| (source.name(), destination.name()))
+        .sorted()
+        .dedup()
+        .collect::<Vec<(String, String)>>();
+
+    for (source, destination) in edges {
+        dot.push_str(&format!(
+            "    {} -> {};\n",
+            dot_id(&source),
+            dot_id(&destination)
+        ));
+    }
+
+    if include_broken {
+        let taken = names.iter().map(String::as_str).collect::<HashSet<&str>>();
+        let broken = broken_edges(&index);
+
+        for destination in broken.iter().map(|(_, d, _)| d).unique().sorted() {
+            dot.push_str(&format!(
+                "    {} [label={}, style=dashed, color=red, fontcolor=red];\n",
+                dot_id(&broken_id(destination, &taken)),
+                dot_id(destination)
+            ));
+        }
+        for (source, destination, _) in &broken {
+            dot.push_str(&format!(
+                "    {} -> {} [style=dashed, color=red];\n",
+                dot_id(&note_name(source)),
+                dot_id(&broken_id(destination, &taken))
+            ));
+        }
+    }
+
+    dot.push_str("}\n");
+    dot
+}
+
+/// Every pair of a note and a destination it links to that doesn't resolve,
+/// with the number of links between them, sorted by note path and then
+/// destination.
+fn broken_edges(index: &VaultIndex) -> Vec<(PathBuf, String, usize)> {
+    index
+        .broken_links()
+        .into_iter()
+        .map(|l| (l.source, l.destination))
+        .counts()
+        .into_iter()
+        .map(|((source, destination), count)| (source, destination, count))
+        .sorted()
+        .collect()
+}
+
+/// The id of the node standing for the unresolved `destination`: the
+/// destination after `broken:`, with more of those in front if a note's
+/// node already has that id. `taken` holds the ids of the notes' nodes.
+fn broken_id(destination: &str, taken: &HashSet<&str>) -> String {
+    let mut id = format!("broken:{}", destination);
+    while taken.contains(id.as_str()) {
+        id = format!("broken:{}", id);
+    }
+
+    id
+}
+
+/// The name of the note at `path`, as `Note::name` would give it.
+fn note_name(path: &Path) -> String {
+    match path.file_stem() {
+        Some(stem) => stem.to_string_lossy().to_string(),
+        None => String::new(),
+    }
+}
+
+/// Quotes `id` as a DOT identifier.
+fn dot_id(id: &str) -> String {
+    format!("\"{}\"", id.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// The link graph as GraphML, for Gephi or yEd. Nodes are identified by
+/// their path relative to the vault root and carry the note's `label`, its
+/// `folder` and its `inbound` count of linking notes, as in
+/// `Vault::inbound_counts`. Each linked pair of notes is one directed edge
+/// whose `weight` counts the links joining them.
+///
+/// With `include_broken`, each unresolved destination also becomes a node,
+/// with an id from `broken_id`, labelled with the destination and with its
+/// `broken` attribute set, and gets an edge from each note referring to it.
+pub fn graphml(vault: &Vault, include_broken: bool) -> String {
+    let mut graphml = String::from(concat!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n",
+        "<graphml xmlns=\"http://graphml.graphdrawing.org/xmlns\">\n",
+        "  <key id=\"label\" for=\"node\" attr.name=\"label\" attr.type=\"string\"/>\n",
+        "  <key id=\"folder\" for=\"node\" attr.name=\"folder\" attr.type=\"string\"/>\n",
+        "  <key id=\"inbound\" for=\"node\" attr.name=\"inbound\" attr.type=\"int\"/>\n",
+    ));
+    if include_broken {
+        graphml.push_str(concat!(
+            "  <key id=\"broken\" for=\"node\" attr.name=\"broken\" attr.type=\"boolean\">\n",
+            "    <default>false</default>\n",
+            "  </key>\n",
+        ));
+    }
+    graphml.push_str(concat!(
+        "  <key id=\"weight\" for=\"edge\" attr.name=\"weight\" attr.type=\"int\"/>\n",
+        "  <graph id=\"vault\" edgedefault=\"directed\">\n",
+    ));
+
+    let index = vault.index();
+    let edges = index.weighted_edges();
+    let mut inbound = HashMap::new();
+    for (source, destination, _) in &edges {
+        if source.path() != destination.path() {
+            *inbound.entry(destination.path()).or_insert(0) += 1;
+        }
+    }
+
+    for note in vault.notes() {
+        let path = vault.relative_path(note.path());
+        let folder = path.parent().map(|f| f.display().to_string());
+        graphml.push_str(&format!(
+            concat!(
+                "    <node id=\"{}\">\n",
+                "      <data key=\"label\">{}</data>\n",
+                "      <data key=\"folder\">{}</data>\n",
+                "      <data key=\"inbound\">{}</data>\n",
+                "    </node>\n",
+            ),
+            xml_escape(&path.display().to_string()),
+            xml_escape(&note.name()),
+            xml_escape(&folder.unwrap_or_default()),
+            inbound.get(note.path()).unwrap_or(&0),
+        ));
+    }
+
+    for (source, destination, count) in edges {
+        graphml.push_str(&format!(
+            concat!(
+                "    <edge source=\"{}\" target=\"{}\">\n",
+                "      <data key=\"weight\">{}</data>\n",
+                "    </edge>\n",
+            ),
+            xml_escape(&vault.relative_path(source.path()).display().to_string()),
+            xml_escape(
+                &vault
+                    .relative_path(destination.path())
+                    .display()
+                    .to_string()
+            ),
+            count,
+        ));
+    }
+
+    if include_broken {
+        let ids = vault
+            .notes()
+            .iter()
+            .map(|n| vault.relative_path(n.path()).display().to_string())
+            .collect::<Vec<String>>();
+        let taken = ids.iter().map(String::as_str).collect::<HashSet<&str>>();
+        let broken = broken_edges(&index);
+        let inbound = broken.iter().map(|(_, d, _)| d).counts();
+
+        for destination in broken.iter().map(|(_, d, _)| d).unique().sorted() {
+            graphml.push_str(&format!(
+                concat!(
+                    "    <node id=\"{}\">\n",
+                    "      <data key=\"label\">{}</data>\n",
+                    "      <data key=\"folder\"></data>\n",
+                    "      <data key=\"inbound\">{}</data>\n",
+                    "      <data key=\"broken\">true</data>\n",
+                    "    </node>\n",
+                ),
+                xml_escape(&broken_id(destination, &taken)),
+                xml_escape(destination),
+                inbound[destination],
+            ));
+        }
+        for (source, destination, count) in &broken {
+            graphml.push_str(&format!(
+                concat!(
+                    "    <edge source=\"{}\" target=\"{}\">\n",
+                    "      <data key=\"weight\">{}</data>\n",
+                    "    </edge>\n",
+                ),
+                xml_escape(&vault.relative_path(source).display().to_string()),
+                xml_escape(&broken_id(destination, &taken)),
+                count,
+            ));
+        }
+    }
+
+    graphml.push_str("  </graph>\n</graphml>\n");
+    graphml
+}
+
+/// Escapes `text` for use in XML text or a quoted attribute. Control
+/// characters XML can't represent at all are dropped.
+fn xml_escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            '\t' | '\n' | '\r' => escaped.push(c),
+            c if c < ' ' => {}
+            c => escaped.push(c),
+        }
+    }
+
+    escaped
+}
+
+#[derive(Serialize)]
+struct D3Graph {
+    nodes: Vec<D3Node>,
+    links: Vec<D3Link>,
+}
+
+#[derive(Serialize)]
+struct D3Node {
+    id: String,
+    name: String,
+    group: String,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    broken: bool,
+}
+
+#[derive(Serialize)]
+struct D3Link {
+    source: String,
+    target: String,
+    value: usize,
+}
+
+/// The link graph in the `{"nodes": [...], "links": [...]}` shape D3's force
+/// layout expects. Every note is a node, identified by its path relative to
+/// the vault root, labelled with its `name` and grouped by the folder it is
+/// in. Each linked pair of notes is one link whose `value` counts the links
+/// joining them.
+///
+/// With `include_broken`, each unresolved destination also becomes a node
+/// marked `"broken": true`, with an id from `broken_id` and named after the
+/// destination, linked from the notes that refer to it.
+pub fn d3_json(vault: &Vault, include_broken: bool) -> serde_json::Result<String> {
+    let index = vault.index();
+    let id = |path: &Path| vault.relative_path(path).display().to_string();
+
+    let mut nodes = vault
+        .notes()
+        .iter()
+        .map(|n| {
+            let folder = vault.relative_path(n.path()).parent();
+            D3Node {
+                id: id(n.path()),
+                name: n.name(),
+                group: folder.map(|f| f.display().to_string()).unwrap_or_default(),
+                broken: false,
+            }
+        })
+        .sorted_by(|a, b| a.id.cmp(&b.id))
+        .collect::<Vec<D3Node>>();
+
+    let mut links = index
+        .weighted_edges()
+        .iter()
+        .map(|(source, destination, count)| D3Link {
+            source: id(source.path()),
+            target: id(destination.path()),
+            value: *count,
+        })
+        .collect::<Vec<D3Link>>();
+
+    if include_broken {
+        let ids = nodes.iter().map(|n| n.id.clone()).collect::<Vec<String>>();
+        let taken = ids.iter().map(String::as_str).collect::<HashSet<&str>>();
+        let broken = broken_edges(&index);
+
+        for destination in broken.iter().map(|(_, d, _)| d).unique().sorted() {
+            nodes.push(D3Node {
+                id: broken_id(destination, &taken),
+                name: destination.clone(),
+                group: String::new(),
+                broken: true,
+            });
+        }
+        for (source, destination, value) in &broken {
+            links.push(D3Link {
+                source: id(source),
+                target: broken_id(destination, &taken),
+                value: *value,
+            });
+        }
+    }
+
+    serde_json::to_string(&D3Graph { nodes, links })
+}
+
+#[derive(Serialize)]
+struct CsvLink<'a> {
+    source: String,
+    destination: &'a str,
+    text: &'a str,
+    is_embed: bool,
+    heading: Option<&'a str>,
+}
+
+/// Writes every link in the vault as CSV with the columns
+/// `source,destination,text,is_embed,heading`. Sources are relative to the
+/// vault root.
+pub fn links_csv<W: Write>(vault: &Vault, writer: W, header: bool) -> csv::Result<()> {
+    let mut writer = csv::WriterBuilder::new()
+        .has_headers(header)
+        .from_writer(writer);
+
+    for link in vault.links() {
+        writer.serialize(CsvLink {
+            source: vault.relative_path(&link.source).display().to_string(),
+            destination: &link.destination,
+            text: &link.text,
+            is_embed: link.is_embed,
+            heading: link.heading.as_deref(),
+        })?;
+    }
+
+    writer.flush()?;
+    Ok(())
+}
+
+const SQLITE_SCHEMA: &str = "
+    DROP TABLE IF EXISTS notes;
+    DROP TABLE IF EXISTS links;
+    DROP TABLE IF EXISTS aliases;
+    DROP TABLE IF EXISTS tags;
+
+    CREATE TABLE notes (path TEXT PRIMARY KEY, name TEXT NOT NULL);
+    CREATE TABLE links (
+        source TEXT NOT NULL,
+        destination TEXT NOT NULL,
+        text TEXT NOT NULL,
+        kind TEXT NOT NULL
+    );
+    CREATE TABLE aliases (note TEXT NOT NULL, alias TEXT NOT NULL);
+    CREATE TABLE tags (note TEXT NOT NULL, tag TEXT NOT NULL);
+
+    CREATE INDEX notes_name ON notes (name);
+    CREATE INDEX links_source ON links (source);
+    CREATE INDEX links_destination ON links (destination);
+    CREATE INDEX aliases_note ON aliases (note);
+    CREATE INDEX aliases_alias ON aliases (alias);
+    CREATE INDEX tags_note ON tags (note);
+    CREATE INDEX tags_tag ON tags (tag);
+";
+
+/// Writes the vault to the SQLite database at `path` as the tables
+/// `notes(path, name)`, `links(source, destination, text, kind)`,
+/// `aliases(note, alias)` and `tags(note, tag)`, replacing any tables of
+/// those names already there.
+///
+/// Note paths are relative to the vault root, and `links.source`,
+/// `aliases.note` and `tags.note` each hold one. `kind` is one of `wiki`,
+/// `inline`, `reference` or `autolink`. A note has one row in `tags` for
+/// each of its tags, however often it appears.
+pub fn sqlite(vault: &Vault, path: &Path) -> rusqlite::Result<()> {
+    let mut connection = rusqlite::Connection::open(path)?;
+    // One transaction rather than one per row, and the old tables stay put
+    // if anything fails.
+    let transaction = connection.transaction()?;
+    transaction.execute_batch(SQLITE_SCHEMA)?;
+
+    {
+        let mut insert_note = transaction.prepare("INSERT INTO notes VALUES (?1, ?2)")?;
+        let mut insert_link = transaction.prepare("INSERT INTO links VALUES (?1, ?2, ?3, ?4)")?;
+        let mut insert_alias = transaction.prepare("INSERT INTO aliases VALUES (?1, ?2)")?;
+        let mut insert_tag = transaction.prepare("INSERT INTO tags VALUES (?1, ?2)")?;
+
+        for note in vault.notes() {
+            let path = vault.relative_path(note.path()).display().to_string();
+            insert_note.execute((&path, note.name()))?;
+
+            for link in note.links() {
+                let kind = format!("{:?}", link.kind).to_lowercase();
+                insert_link.execute((&path, &link.destination, &link.text, kind))?;
+            }
+            for alias in note.aliases() {
+                insert_alias.execute((&path, alias))?;
+            }
+            for tag in note.tags().into_iter().unique() {
+                insert_tag.execute((&path, tag))?;
+            }
+        }
+    }
+
+    transaction.commit()
+}
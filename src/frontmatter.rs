@@ -0,0 +1,238 @@
+use serde::Serialize;
+use std::collections::BTreeMap;
+use tree_sitter::Node;
+
+/// A frontmatter value, from YAML, TOML or JSON frontmatter alike.
+///
+/// Plain YAML scalars are coerced the way YAML does it, so `3` is an
+/// `Integer`, `1.5` a `Float`, `true` a `Bool` and `null` or `~` is `Null`.
+/// Quoted scalars are always strings.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(untagged)]
+pub enum FrontmatterValue {
+    Null,
+    Bool(bool),
+    Integer(i64),
+    Float(f64),
+    String(String),
+    List(Vec<FrontmatterValue>),
+    Map(BTreeMap<String, FrontmatterValue>),
+}
+
+impl FrontmatterValue {
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            FrontmatterValue::String(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// Whether the value equals `value` as written on a command line. Numbers
+    /// and booleans compare by their text, and a list matches if any of its
+    /// items do, so `tags: [a, b]` matches `a`. Maps never match.
+    pub fn matches(&self, value: &str) -> bool {
+        match self {
+            FrontmatterValue::Null => value.is_empty() || value == "null",
+            FrontmatterValue::Bool(b) => b.to_string().eq_ignore_ascii_case(value),
+            FrontmatterValue::Integer(i) => value.parse() == Ok(*i),
+            FrontmatterValue::Float(f) => value.parse() == Ok(*f),
+            FrontmatterValue::String(s) => s == value,
+            FrontmatterValue::List(items) => items.iter().any(|i| i.matches(value)),
+            FrontmatterValue::Map(_) => false,
+        }
+    }
+
+    /// Builds the value of a YAML node, where `source` is the text the node
+    /// was parsed from.
+    pub(crate) fn from_yaml(node: Node, source: &str) -> Self {
+        let text = &source[node.byte_range()];
+
+        match node.kind() {
+            "flow_node" | "block_node" => {
+                // Skip any `!tag` or `&anchor` in front of the value itself.
+                let mut cursor = node.walk();
+                let value = node
+                    .named_children(&mut cursor)
+                    .filter(|c| c.kind() != "tag" && c.kind() != "anchor")
+                    .last();
+
+                match value {
+                    Some(value) => Self::from_yaml(value, source),
+                    None => FrontmatterValue::Null,
+                }
+            }
+            "plain_scalar" => match node.named_child(0).map(|c| c.kind()) {
+                Some("null_scalar") => FrontmatterValue::Null,
+                Some("boolean_scalar") => FrontmatterValue::Bool(text.eq_ignore_ascii_case("true")),
+                Some("integer_scalar") => match text.parse() {
+                    Ok(value) => FrontmatterValue::Integer(value),
+                    Err(_) => FrontmatterValue::String(text.to_string()),
+                },
+                Some("float_scalar") => match text.parse() {
+                    Ok(value) => FrontmatterValue::Float(value),
+                    Err(_) => FrontmatterValue::String(text.to_string()),
+                },
+                _ => FrontmatterValue::String(text.to_string()),
+            },
+            "single_quote_scalar" | "double_quote_scalar" => {
+                FrontmatterValue::String(unquote(text))
+            }
+            "block_scalar" => FrontmatterValue::String(block_scalar(text)),
+            "block_sequence" | "flow_sequence" => {
+                let mut cursor = node.walk();
+                let items = node
+                    .named_children(&mut cursor)
+                    .map(|item| match item.kind() {
+                        // `- value` wraps the value in one more node.
+                        "block_sequence_item" => match item.named_child(0) {
+                            Some(value) => Self::from_yaml(value, source),
+                            None => FrontmatterValue::Null,
+                        },
+                        _ => Self::from_yaml(item, source),
+                    })
+                    .collect();
+
+                FrontmatterValue::List(items)
+            }
+            "block_mapping" | "flow_mapping" => {
+                let mut map = BTreeMap::new();
+                for (key, value) in mapping_pairs(node) {
+                    let value = match value {
+                        Some(value) => Self::from_yaml(value, source),
+                        None => FrontmatterValue::Null,
+                    };
+                    map.insert(key_text(key, source), value);
+                }
+
+                FrontmatterValue::Map(map)
+            }
+            _ => FrontmatterValue::String(text.to_string()),
+        }
+    }
+}
+
+impl From<&serde_json::Value> for FrontmatterValue {
+    fn from(value: &serde_json::Value) -> Self {
+        match value {
+            serde_json::Value::Null => FrontmatterValue::Null,
+            serde_json::Value::Bool(value) => FrontmatterValue::Bool(*value),
+            serde_json::Value::Number(number) => match number.as_i64() {
+                Some(value) => FrontmatterValue::Integer(value),
+                None => FrontmatterValue::Float(number.as_f64().unwrap_or(f64::NAN)),
+            },
+            serde_json::Value::String(value) => FrontmatterValue::String(value.clone()),
+            serde_json::Value::Array(values) => {
+                FrontmatterValue::List(values.iter().map(FrontmatterValue::from).collect())
+            }
+            serde_json::Value::Object(map) => FrontmatterValue::Map(
+                map.iter()
+                    .map(|(key, value)| (key.clone(), FrontmatterValue::from(value)))
+                    .collect(),
+            ),
+        }
+    }
+}
+
+/// The value of the top-level `key` in a parsed YAML document.
+pub(crate) fn yaml_value(root: Node, source: &str, key: &str) -> Option<FrontmatterValue> {
+    let mapping = top_level_mapping(root)?;
+
+    mapping_pairs(mapping)
+        .into_iter()
+        .find(|(k, _)| key_text(*k, source) == key)
+        .map(|(_, value)| match value {
+            Some(value) => FrontmatterValue::from_yaml(value, source),
+            None => FrontmatterValue::Null,
+        })
+}
+
+/// The mapping at the top of the first document, if the frontmatter is one.
+fn top_level_mapping(root: Node) -> Option<Node> {
+    let mut node = root;
+
+    loop {
+        match node.kind() {
+            "block_mapping" | "flow_mapping" => return Some(node),
+            "stream" | "document" | "block_node" | "flow_node" => {
+                let mut cursor = node.walk();
+                let child = node
+                    .named_children(&mut cursor)
+                    .find(|c| c.kind() != "comment" && c.kind() != "tag" && c.kind() != "anchor");
+                node = child?;
+            }
+            _ => return None,
+        }
+    }
+}
+
+/// The key and, unless it was left empty, the value of every pair in a block
+/// or flow mapping.
+fn mapping_pairs(mapping: Node) -> Vec<(Node, Option<Node>)> {
+    let mut cursor = mapping.walk();
+
+    mapping
+        .named_children(&mut cursor)
+        .filter(|pair| pair.kind() == "block_mapping_pair" || pair.kind() == "flow_pair")
+        .filter_map(|pair| {
+            let key = pair.child_by_field_name("key")?;
+            Some((key, pair.child_by_field_name("value")))
+        })
+        .collect()
+}
+
+fn key_text(key: Node, source: &str) -> String {
+    match FrontmatterValue::from_yaml(key, source) {
+        FrontmatterValue::String(key) => key,
+        _ => source[key.byte_range()].to_string(),
+    }
+}
+
+/// The contents of a single or double quoted scalar, with its escapes
+/// resolved.
+fn unquote(text: &str) -> String {
+    if let Some(inner) = text.strip_prefix('\'').and_then(|t| t.strip_suffix('\'')) {
+        return inner.replace("''", "'");
+    }
+
+    let inner = text
+        .strip_prefix('"')
+        .and_then(|t| t.strip_suffix('"'))
+        .unwrap_or(text);
+    let mut unquoted = String::with_capacity(inner.len());
+    let mut chars = inner.chars();
+
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unquoted.push(c);
+            continue;
+        }
+
+        match chars.next() {
+            Some('n') => unquoted.push('\n'),
+            Some('t') => unquoted.push('\t'),
+            Some('0') => unquoted.push('\0'),
+            Some(c) => unquoted.push(c),
+            None => unquoted.push('\\'),
+        }
+    }
+
+    unquoted
+}
+
+/// The text of a `|` or `>` block scalar, with its indentation removed. Folded
+/// scalars keep their line breaks, which is close enough for frontmatter.
+fn block_scalar(text: &str) -> String {
+    let lines = text.lines().skip(1).collect::<Vec<&str>>();
+    let indent = lines
+        .iter()
+        .filter(|l| !l.trim().is_empty())
+        .map(|l| l.len() - l.trim_start().len())
+        .min()
+        .unwrap_or(0);
+
+    lines
+        .iter()
+        .map(|l| l.get(indent..).unwrap_or(""))
+        .collect::<Vec<&str>>()
+        .join("\n")
+}
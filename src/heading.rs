@@ -0,0 +1,31 @@
+use serde::{Deserialize, Serialize};
+
+/// A `# Heading`, or a heading underlined with `===` or `---`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Heading {
+    /// 1 for `#` or `===`, up to 6 for `######`.
+    pub level: u8,
+    /// The heading's text as written, without its `#` markers.
+    pub text: String,
+    /// Zero-based line the heading starts on.
+    pub line: usize,
+}
+
+/// The form headings are compared in, so that `[[Note#My heading]]`,
+/// `[[Note#my-heading]]` and `[text](Note.md#My%20Heading)` all match
+/// `## My Heading`. Case is ignored, spaces, dashes and underscores all
+/// separate words, and any other punctuation is dropped.
+pub(crate) fn heading_key(heading: &str) -> String {
+    heading
+        .replace("%20", " ")
+        .to_lowercase()
+        .split(|c: char| c.is_whitespace() || c == '-' || c == '_')
+        .map(|word| {
+            word.chars()
+                .filter(|c| c.is_alphanumeric())
+                .collect::<String>()
+        })
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<String>>()
+        .join(" ")
+}
@@ -0,0 +1,449 @@
+use crate::link::{nfc, percent_decode};
+use crate::resolver::Resolver;
+use crate::{Attachment, Direction, FolderStats, Link, Note, Position, Stats, Vault};
+use itertools::Itertools;
+use rayon::prelude::*;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::path::{Path, PathBuf};
+
+/// The links between a vault's notes, resolved once so that lookups don't
+/// each go over the whole vault.
+///
+/// Building an index parses every link in the vault and resolves it, which
+/// costs about as much as a single `Vault::backlinks` call. After that,
+/// `resolve` is a hash lookup and `outlinks` and `backlinks` only touch the
+/// notes they return, so anything asking more than one question of a vault
+/// should build one index and keep it. It borrows the vault, so it has to be
+/// built again after `Vault::reload`.
+pub struct VaultIndex<'a> {
+    vault: &'a Vault,
+    notes: &'a [Note],
+    resolver: Resolver<'a>,
+    by_path: HashMap<&'a Path, usize>,
+    /// For each note, the notes it links to, in the order first linked.
+    outlinks: Vec<Vec<usize>>,
+    /// For each note, the notes linking to it, in path order.
+    backlinks: Vec<Vec<usize>>,
+}
+
+impl<'a> VaultIndex<'a> {
+    pub(crate) fn new(vault: &'a Vault) -> Self {
+        let notes = vault.notes();
+        let resolver = Resolver::new(vault.roots(), notes);
+        let by_path = notes
+            .iter()
+            .enumerate()
+            .map(|(i, n)| (n.path(), i))
+            .collect::<HashMap<&Path, usize>>();
+
+        let outlinks = notes
+            .par_iter()
+            .map(|n| {
+                n.links()
+                    .iter()
+                    .filter_map(|l| resolver.resolve_link(l))
+                    .map(|destination| by_path[destination.path()])
+                    .unique()
+                    .collect::<Vec<usize>>()
+            })
+            .collect::<Vec<Vec<usize>>>();
+
+        // Going through sources in order keeps each list sorted by path.
+        let mut backlinks = vec![vec![]; notes.len()];
+        for (source, destinations) in outlinks.iter().enumerate() {
+            for &destination in destinations {
+                backlinks[destination].push(source);
+            }
+        }
+
+        Self {
+            vault,
+            notes,
+            resolver,
+            by_path,
+            outlinks,
+            backlinks,
+        }
+    }
+
+    /// The note `destination` links to, as `Vault::resolve` finds it.
+    pub fn resolve(&self, destination: &str) -> Option<&'a Note> {
+        self.resolver.resolve(destination)
+    }
+
+    /// Every note `destination` could refer to, best match first.
+    pub fn resolve_all(&self, destination: &str) -> Vec<&'a Note> {
+        self.resolver.resolve_all(destination)
+    }
+
+    /// The note `link` points at, as `Vault::resolve_link` finds it.
+    pub fn resolve_link(&self, link: &Link) -> Option<&'a Note> {
+        self.resolver.resolve_link(link)
+    }
+
+    /// Notes that `note`, which may be anything `resolve` accepts, links to,
+    /// each once and in the order first linked.
+    pub fn outlinks(&self, note: &str) -> Vec<&'a Note> {
+        match self.position(note) {
+            Some(i) => self.outlinks[i].iter().map(|&j| &self.notes[j]).collect(),
+            None => vec![],
+        }
+    }
+
+    /// Notes containing at least one link to `note`, which may be anything
+    /// `resolve` accepts, each once and sorted by path.
+    pub fn backlinks(&self, note: &str) -> Vec<&'a Note> {
+        match self.position(note) {
+            Some(i) => self.backlinks[i].iter().map(|&j| &self.notes[j]).collect(),
+            None => vec![],
+        }
+    }
+
+    /// How many other notes link to each note, keyed by note path. Notes
+    /// nothing links to are left out.
+    pub fn inbound_counts(&self) -> HashMap<PathBuf, usize> {
+        self.backlinks
+            .iter()
+            .enumerate()
+            .map(|(i, sources)| (i, sources.iter().filter(|&&j| j != i).count()))
+            .filter(|(_, count)| *count > 0)
+            .map(|(i, count)| (self.notes[i].path().to_path_buf(), count))
+            .collect()
+    }
+
+    /// Distinct `(source, destination)` note pairs joined by at least one
+    /// resolved link, by source path and then in the order first linked.
+    pub fn edges(&self) -> Vec<(&'a Note, &'a Note)> {
+        self.outlinks
+            .iter()
+            .enumerate()
+            .flat_map(|(i, destinations)| {
+                destinations
+                    .iter()
+                    .map(move |&j| (&self.notes[i], &self.notes[j]))
+            })
+            .collect()
+    }
+
+    /// Like `edges`, with the number of links joining each pair.
+    pub fn weighted_edges(&self) -> Vec<(&'a Note, &'a Note, usize)> {
+        self.notes
+            .iter()
+            .flat_map(|n| {
+                n.links()
+                    .iter()
+                    .filter_map(|l| self.resolver.resolve_link(l))
+                    .map(|destination| (n, destination))
+                    .collect::<Vec<(&Note, &Note)>>()
+            })
+            .sorted_by_key(|(source, destination)| (source.path(), destination.path()))
+            .dedup_by_with_count(|a, b| a.0.path() == b.0.path() && a.1.path() == b.1.path())
+            .map(|(count, (source, destination))| (source, destination, count))
+            .collect()
+    }
+
+    /// Links to files other than notes, with the file each one refers to,
+    /// as `Vault::attachments` explains.
+    pub fn attachments(&self) -> Vec<Attachment> {
+        let vault = self.vault;
+        let files = vault.files();
+        let mut by_name = HashMap::<String, Vec<&PathBuf>>::new();
+        for file in &files {
+            if let Some(name) = file.file_name() {
+                let name = nfc(&name.to_string_lossy()).to_lowercase();
+                by_name.entry(name).or_default().push(file);
+            }
+        }
+        for candidates in by_name.values_mut() {
+            candidates.sort_by_key(|p| (p.components().count(), p.as_os_str().len()));
+        }
+
+        vault
+            .links()
+            .into_iter()
+            .filter(|l| !l.is_external() && !l.destination.is_empty())
+            .filter(|l| {
+                Path::new(&l.destination).extension().is_some()
+                    && !vault.options().has_extension(Path::new(&l.destination))
+            })
+            .filter(|l| self.resolver.resolve_link(l).is_none())
+            .map(|link| {
+                let destination = PathBuf::from(percent_decode(&link.destination));
+                let root = vault.root_of(&link.source).unwrap_or(vault.root());
+                let folder = link.source.parent().unwrap_or(root);
+
+                let path = [root.join(&destination), folder.join(&destination)]
+                    .into_iter()
+                    .find(|p| p.is_file())
+                    .or_else(|| {
+                        let name = nfc(&destination.file_name()?.to_string_lossy()).to_lowercase();
+                        by_name.get(&name).map(|c| c[0].clone())
+                    });
+
+                Attachment { link, path }
+            })
+            .collect()
+    }
+
+    /// Files in the vault, other than notes, that no link refers to.
+    pub fn unused_attachments(&self) -> Vec<PathBuf> {
+        let canonical = |p: &Path| std::fs::canonicalize(p).unwrap_or(p.to_path_buf());
+        let used = self
+            .attachments()
+            .iter()
+            .filter_map(|a| a.path())
+            .map(canonical)
+            .collect::<HashSet<PathBuf>>();
+
+        self.vault
+            .files()
+            .into_iter()
+            .filter(|f| !used.contains(&canonical(f)))
+            .collect()
+    }
+
+    /// Links that don't resolve to a note or an attachment that exists, as
+    /// `Vault::broken_links` explains.
+    pub fn broken_links(&self) -> Vec<Link> {
+        let found = self.found_attachments();
+
+        self.vault
+            .links()
+            .into_iter()
+            .filter(|l| !l.is_external() && !l.destination.is_empty())
+            .filter(|l| self.resolver.resolve_link(l).is_none())
+            .filter(|l| !found.contains(&(l.source.clone(), l.start)))
+            .collect()
+    }
+
+    /// The source and start of every link to an attachment that exists.
+    /// Those links don't resolve to a note, but aren't broken either.
+    fn found_attachments(&self) -> HashSet<(PathBuf, Position)> {
+        self.attachments()
+            .into_iter()
+            .filter(|a| !a.is_missing())
+            .map(|a| (a.link.source, a.link.start))
+            .collect()
+    }
+
+    /// Links that resolve to the note they are in, as `Vault::self_links`
+    /// explains.
+    pub fn self_links(&self) -> Vec<Link> {
+        self.vault
+            .links()
+            .into_iter()
+            .filter(|l| l.heading.is_none() && l.block.is_none())
+            .filter(|l| {
+                self.resolver
+                    .resolve_link(l)
+                    .is_some_and(|n| n.path() == l.source)
+            })
+            .collect()
+    }
+
+    /// Links to a note lacking the `#Heading` or `#^block` they point at, as
+    /// `Vault::broken_anchors` explains.
+    pub fn broken_anchors(&self) -> Vec<Link> {
+        self.notes
+            .iter()
+            .flat_map(|n| {
+                n.links()
+                    .into_iter()
+                    .filter(|l| l.heading.is_some() || l.block.is_some())
+                    .filter(|l| {
+                        let target = match l.destination.is_empty() {
+                            true => Some(n),
+                            false => self.resolver.resolve_link(l),
+                        };
+
+                        match (target, &l.heading, &l.block) {
+                            (Some(target), Some(heading), _) => !target.has_heading(heading),
+                            (Some(target), _, Some(block)) => !target.block_ids().contains(block),
+                            _ => false,
+                        }
+                    })
+                    .collect::<Vec<Link>>()
+            })
+            .collect()
+    }
+
+    /// Notes that neither link anywhere nor are linked to by another note.
+    pub fn orphans(&self) -> Vec<&'a Note> {
+        self.notes
+            .iter()
+            .enumerate()
+            .filter(|(i, n)| !self.is_linked(*i) && n.links().is_empty())
+            .map(|(_, n)| n)
+            .collect()
+    }
+
+    /// Notes that no other note links to, regardless of their own links.
+    pub fn unreferenced(&self) -> Vec<&'a Note> {
+        self.notes
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| !self.is_linked(*i))
+            .map(|(_, n)| n)
+            .collect()
+    }
+
+    /// Whether a note other than the one at `i` links to it.
+    fn is_linked(&self, i: usize) -> bool {
+        self.backlinks[i].iter().any(|&j| j != i)
+    }
+
+    pub fn stats(&self) -> Stats {
+        let links = self.vault.links();
+        let resolved_links = links
+            .iter()
+            .filter(|l| self.resolver.resolve_link(l).is_some())
+            .count();
+
+        let links_per_note = match self.notes.len() {
+            0 => 0.0,
+            notes => links.len() as f64 / notes as f64,
+        };
+
+        Stats {
+            notes: self.notes.len(),
+            outbound_links: links.len(),
+            resolved_links,
+            external_links: links.iter().filter(|l| l.is_external()).count(),
+            broken_links: self.broken_links().len(),
+            unique_targets: self.vault.targets().len(),
+            orphans: self.orphans().len(),
+            links_per_note,
+        }
+    }
+
+    /// `stats` for each folder directly under the vault root, as
+    /// `Vault::folder_stats` explains.
+    pub fn folder_stats(&self) -> Vec<FolderStats> {
+        let orphans = self
+            .orphans()
+            .iter()
+            .map(|n| n.path())
+            .collect::<HashSet<&Path>>();
+
+        self.notes
+            .iter()
+            .into_group_map_by(|n| {
+                let relative = self.vault.relative_to_root(n.path());
+                match relative.components().count() > 1 {
+                    true => relative
+                        .components()
+                        .next()
+                        .map(|c| c.as_os_str().to_string_lossy().to_string())
+                        .unwrap_or_default(),
+                    false => String::new(),
+                }
+            })
+            .into_iter()
+            .map(|(folder, notes)| FolderStats {
+                folder,
+                notes: notes.len(),
+                links: notes.iter().map(|n| n.links().len()).sum(),
+                orphans: notes.iter().filter(|n| orphans.contains(n.path())).count(),
+            })
+            .sorted_by(|a, b| b.notes.cmp(&a.notes).then_with(|| a.folder.cmp(&b.folder)))
+            .collect()
+    }
+
+    /// Every note within `depth` links of `note`, with its distance from it,
+    /// as `Vault::neighbors` explains.
+    pub fn neighbors(
+        &self,
+        note: &str,
+        depth: usize,
+        direction: Direction,
+    ) -> Vec<(&'a Note, usize)> {
+        let start = match self.position(note) {
+            Some(start) => start,
+            None => return vec![],
+        };
+        let adjacent = |i: usize| {
+            let out = match direction {
+                Direction::In => &[][..],
+                _ => &self.outlinks[i][..],
+            };
+            let inward = match direction {
+                Direction::Out => &[][..],
+                _ => &self.backlinks[i][..],
+            };
+            out.iter().chain(inward)
+        };
+
+        // Breadth first, so each note is reached by its shortest path and
+        // visited once however many cycles it is on.
+        let mut distances = vec![None; self.notes.len()];
+        let mut queue = VecDeque::from([start]);
+        distances[start] = Some(0);
+        while let Some(i) = queue.pop_front() {
+            let distance = distances[i].unwrap_or(0);
+            if distance == depth {
+                continue;
+            }
+            for &j in adjacent(i) {
+                if distances[j].is_none() {
+                    distances[j] = Some(distance + 1);
+                    queue.push_back(j);
+                }
+            }
+        }
+
+        // Notes are sorted by path, so a stable sort by distance is enough.
+        self.notes
+            .iter()
+            .zip(distances)
+            .filter_map(|(note, distance)| Some((note, distance?)))
+            .sorted_by_key(|(_, distance)| *distance)
+            .collect()
+    }
+
+    /// Every simple cycle of at most `max_len` notes in the link graph, each
+    /// once, starting from whichever of its notes sorts first by path.
+    /// Shorter cycles come first. Notes linking to themselves aren't cycles.
+    pub fn cycles(&self, max_len: usize) -> Vec<Vec<&'a Note>> {
+        let mut cycles = vec![];
+
+        // A cycle is only followed from its first note, through notes after
+        // it, so none is found twice.
+        for start in 0..self.notes.len() {
+            let mut path = vec![start];
+            self.extend_cycles(start, &mut path, max_len, &mut cycles);
+        }
+        cycles.sort_by_key(|cycle| cycle.len());
+
+        cycles
+            .into_iter()
+            .map(|cycle| cycle.into_iter().map(|i| &self.notes[i]).collect())
+            .collect()
+    }
+
+    fn extend_cycles(
+        &self,
+        start: usize,
+        path: &mut Vec<usize>,
+        max_len: usize,
+        cycles: &mut Vec<Vec<usize>>,
+    ) {
+        let last = path[path.len() - 1];
+
+        for &next in &self.outlinks[last] {
+            if next == start && path.len() > 1 {
+                cycles.push(path.clone());
+            } else if next > start && path.len() < max_len && !path.contains(&next) {
+                path.push(next);
+                self.extend_cycles(start, path, max_len, cycles);
+                path.pop();
+            }
+        }
+    }
+
+    /// Where the note `note` resolves to is in the vault's notes.
+    fn position(&self, note: &str) -> Option<usize> {
+        let note = self.resolve(note)?;
+
+        self.by_path.get(note.path()).copied()
+    }
+}
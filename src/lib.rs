@@ -0,0 +1,36 @@
+mod attachment;
+mod cache;
+mod callout;
+mod capture;
+mod daily;
+mod error;
+pub mod export;
+mod frontmatter;
+mod heading;
+mod index;
+mod link;
+mod note;
+mod query_cache;
+mod rename;
+mod resolver;
+mod stats;
+mod target;
+mod task;
+mod vault;
+
+pub use attachment::Attachment;
+pub use callout::Callout;
+pub use capture::{Capture, Grammar};
+pub use daily::{DateRange, DAILY_FORMAT};
+pub use error::DarkstoneError;
+pub use frontmatter::FrontmatterValue;
+pub use heading::Heading;
+pub use index::VaultIndex;
+pub use link::{Link, LinkKind, Position};
+pub use note::{Note, NoteSummary};
+pub use query_cache::QueryCache;
+pub use rename::{Edit, Rename};
+pub use stats::{FolderStats, Stats};
+pub use target::Target;
+pub use task::Task;
+pub use vault::{Direction, ScanOptions, Timings, Vault};
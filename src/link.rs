@@ -0,0 +1,209 @@
+use serde::{Deserialize, Serialize};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use unicode_normalization::UnicodeNormalization;
+
+/// The markdown syntax a link was written in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum LinkKind {
+    /// `[[Note]]` or `[[Note|text]]`
+    Wiki,
+    /// `[text](destination)`
+    Inline,
+    /// `[text][label]`, `[label][]` or `[label]`, with the destination taken
+    /// from a matching `[label]: destination` definition
+    Reference,
+    /// `<https://...>` or `<name@example.com>`
+    Autolink,
+}
+
+/// A point in a note's source. Lines and columns count from zero, like an
+/// editor's API, and columns are measured in bytes.
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+pub struct Position {
+    pub line: usize,
+    pub column: usize,
+    /// Offset from the start of the file, not counting any byte order mark.
+    pub byte: usize,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Link {
+    /// Path of the note the link was found in.
+    pub source: PathBuf,
+    pub kind: LinkKind,
+    /// The linked note, without any `#heading` or `#^block` anchor.
+    pub destination: String,
+    /// The destination exactly as written, anchor included.
+    pub raw_destination: String,
+    /// The whole link exactly as written, such as `![[Note|text]]` or
+    /// `[text](<My Note.md>)`, so `[[Note]]` and `[[Note|Note]]` can be told
+    /// apart. It is what `byte_range` covers in the file.
+    pub raw: String,
+    pub text: String,
+    /// Set for `![[...]]` transclusions and `![...](...)` images.
+    pub is_embed: bool,
+    /// Heading from a `[[Note#Heading]]` link.
+    pub heading: Option<String>,
+    /// Block id from a `[[Note#^block]]` link.
+    pub block: Option<String>,
+    /// Where the link starts in the source file, frontmatter included.
+    pub start: Position,
+    /// Where the link ends, exclusive.
+    pub end: Position,
+    /// The bytes of the file holding `destination`, anchor excluded, for
+    /// wiki links, embeds and inline links, and for reference links the
+    /// destination in their `[label]: destination` definition, which other
+    /// links may share. Replacing them retargets the link and leaves the
+    /// rest of it alone. Like `Position::byte`, they don't count a byte
+    /// order mark.
+    pub destination_range: Option<Range<usize>>,
+}
+
+impl Link {
+    pub fn new(
+        source: PathBuf,
+        kind: LinkKind,
+        raw_destination: String,
+        text: String,
+        is_embed: bool,
+        start: Position,
+        end: Position,
+    ) -> Self {
+        let mut link = Self {
+            source,
+            kind,
+            destination: raw_destination.clone(),
+            raw_destination,
+            raw: String::new(),
+            text,
+            is_embed,
+            heading: None,
+            block: None,
+            start,
+            end,
+            destination_range: None,
+        };
+
+        if link.is_external() {
+            return link;
+        }
+
+        if let Some((destination, anchor)) = link.raw_destination.split_once('#') {
+            link.destination = destination.to_string();
+            match anchor.strip_prefix('^') {
+                Some(block) => link.block = Some(block.to_string()),
+                None => link.heading = Some(anchor.to_string()),
+            }
+        }
+
+        link
+    }
+
+    pub fn source(&self) -> &Path {
+        &self.source
+    }
+
+    /// The bytes of the file the whole link takes up.
+    pub fn byte_range(&self) -> Range<usize> {
+        self.start.byte..self.end.byte
+    }
+
+    /// Whether the destination is an `http` or `https` URL. Every such link
+    /// `is_external`.
+    pub fn is_url(&self) -> bool {
+        matches!(self.scheme().as_deref(), Some("http" | "https"))
+    }
+
+    /// The destination the way it is matched to a note: with `%20` style
+    /// escapes decoded for markdown links, which can't hold a bare space,
+    /// and as written for wiki links, which take a `%` literally.
+    pub fn decoded_destination(&self) -> String {
+        match self.kind {
+            LinkKind::Inline | LinkKind::Reference => percent_decode(&self.destination),
+            LinkKind::Wiki | LinkKind::Autolink => self.destination.clone(),
+        }
+    }
+
+    /// Whether the destination is a URL rather than a note in the vault:
+    /// anything with a scheme followed by `//`, such as `https://` or
+    /// `obsidian://`, or a `mailto:` address, with the scheme in any case.
+    pub fn is_external(&self) -> bool {
+        self.scheme().is_some()
+    }
+
+    /// The destination's URL scheme, lowercased, if `is_external` takes it
+    /// for a URL.
+    fn scheme(&self) -> Option<String> {
+        let (scheme, rest) = self.destination.split_once(':')?;
+        let is_scheme = scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+            && scheme
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'));
+        let scheme = scheme.to_ascii_lowercase();
+
+        match is_scheme && (rest.starts_with("//") || scheme == "mailto") {
+            true => Some(scheme),
+            false => None,
+        }
+    }
+}
+
+/// `text` with `%20` style escapes decoded. Escapes that aren't valid UTF-8
+/// once decoded are replaced with U+FFFD.
+pub(crate) fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+
+    String::from_utf8_lossy(&decoded).to_string()
+}
+
+/// `text` in Unicode normalization form C. macOS writes file names
+/// decomposed, so `Café.md` synced from a Mac may not compare equal to a
+/// `[[Café]]` typed elsewhere until both are composed.
+pub(crate) fn nfc(text: &str) -> String {
+    text.nfc().collect()
+}
+
+/// Extensions a link to a note may be written with, and which are dropped
+/// when matching it to the note.
+pub(crate) const NOTE_EXTENSIONS: [&str; 3] = [".md", ".markdown", ".mdx"];
+
+/// `name` without a trailing `.md`, `.markdown` or `.mdx`, in any case. A
+/// name that is nothing but the extension is left alone.
+pub(crate) fn without_extension(name: &str) -> &str {
+    NOTE_EXTENSIONS
+        .iter()
+        .find(|ext| {
+            name.len() > ext.len()
+                && name.is_char_boundary(name.len() - ext.len())
+                && name[name.len() - ext.len()..].eq_ignore_ascii_case(ext)
+        })
+        .map_or(name, |ext| &name[..name.len() - ext.len()])
+}
+
+/// `destination` without its folders or note extension.
+pub(crate) fn target_name(destination: &str) -> &str {
+    without_extension(destination.rsplit('/').next().unwrap_or(destination))
+}
@@ -1,332 +1,2210 @@
-use clap::Parser as ClapParser;
+use anstream::{eprintln, println};
+use anstyle::{AnsiColor, Style};
+use chrono::NaiveDate;
+use clap::{
+    ArgAction, ArgMatches, CommandFactory, FromArgMatches, Parser as ClapParser, Subcommand,
+    ValueEnum,
+};
+use darkstone::{
+    export, DateRange, Direction, Grammar, LinkKind, Note, QueryCache, Rename, ScanOptions, Target,
+    Timings, Vault, DAILY_FORMAT,
+};
+use indicatif::{ProgressBar, ProgressStyle};
 use itertools::Itertools;
+use notify::{Event, EventKind, RecursiveMode, Watcher};
 use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io::{IsTerminal, Read, Write};
 use std::path::{Path, PathBuf};
-use std::sync::Arc;
-use tree_sitter::{Parser, Query, QueryCursor, Tree};
-use tree_sitter_md::{MarkdownParser, MarkdownTree};
-
-#[derive(Debug)]
-struct QueryCache {
-    body_query: Query,
-    alias_query: Query,
-    frontmatter_links: Query,
-}
-
-#[derive(Debug)]
-struct Vault {
-    notes: Vec<Note>,
-}
-
-impl Vault {
-    fn new(path: PathBuf) -> Self {
-        let query_cache = Arc::new(QueryCache {
-            body_query: Query::new(
-                &tree_sitter_md::inline_language(),
-                "(
-                    (
-                        _
-                        [
-                            ((link_destination)? @destination (link_text) @text)
-                            ((link_destination) @destination (link_text)? @text)
-                        ]
-                    )
-                )",
-            )
-            .unwrap(),
-            alias_query: Query::new(
-                &tree_sitter_yaml::language(),
-                "
-                            (
-                              (block_mapping_pair
-                                key: ((flow_node) @key (#match? @key \"aliases\"))
-                                value: (block_node
-                                  (block_sequence
-                                    (block_sequence_item
-                                      (flow_node
-                                        (plain_scalar
-                                          (string_scalar)+ @aliases)))))
-                              )
-                            )
-                        ",
-            )
-            .unwrap(),
-            frontmatter_links: Query::new(
-                &tree_sitter_yaml::language(),
-                "((double_quote_scalar) @scalar)",
+use std::sync::{mpsc, Arc};
+use std::time::{Duration, Instant};
+use tracing::{warn, Level};
+use unicode_normalization::char::is_combining_mark;
+use unicode_normalization::UnicodeNormalization;
+
+/// Find broken links, backlinks, tags and more in a vault of Markdown notes
+///
+/// A vault is a folder of notes linked with wiki links or Markdown links, as
+/// Obsidian keeps them. With no command, darkstone lists every note the vault
+/// links to.
+#[derive(ClapParser, Debug)]
+#[command()]
+struct Args {
+    /// Vault to read, by default the current directory. Give it more than
+    /// once to treat several vaults as one, with links between them resolving
+    #[arg(short, long)]
+    vault_path: Vec<PathBuf>,
+
+    /// File of defaults for --extensions, --exclude, --format,
+    /// --template-folder and --jobs, by default darkstone.toml in the vault
+    /// if there is one. Flags given here override it
+    #[arg(long)]
+    config: Option<PathBuf>,
+
+    /// Output format, also used by commands with a --format of their own
+    /// when they aren't given one
+    #[arg(short, long, value_enum, default_value_t = OutputFormat::Text)]
+    format: OutputFormat,
+
+    /// Print link destinations exactly as written, rather than reduced to
+    /// the note name they point at
+    #[arg(long)]
+    keep_extension: bool,
+
+    /// Order of the target list: natural ignores case and accents and puts
+    /// `Chapter 2` before `Chapter 10`, byte compares the raw UTF-8
+    #[arg(long, value_enum, default_value_t = SortOrder::Natural)]
+    sort: SortOrder,
+
+    /// Comma separated extensions of the files to load as notes
+    #[arg(long, value_delimiter = ',', default_value = "md,markdown,mdx")]
+    extensions: Vec<String>,
+
+    /// Glob, relative to the vault, of files or folders to skip; takes
+    /// precedence over --extensions and may be repeated
+    #[arg(long, value_parser = glob::Pattern::new)]
+    exclude: Vec<glob::Pattern>,
+
+    /// Also scan hidden files and files matched by .gitignore or .ignore
+    #[arg(long)]
+    no_ignore: bool,
+
+    /// Only look this many folders deep for notes, 1 being just the files
+    /// in the vault root; 0 looks everywhere
+    #[arg(long, value_name = "N", default_value_t = 0)]
+    max_depth: usize,
+
+    /// Load notes containing invalid UTF-8, replacing the bad bytes, instead
+    /// of skipping them
+    #[arg(long)]
+    lossy: bool,
+
+    /// Count links inside `<!-- -->` and `%% %%` comments too
+    #[arg(long)]
+    include_comments: bool,
+
+    /// Treat template notes as ordinary notes instead of leaving them out
+    #[arg(long)]
+    include_templates: bool,
+
+    /// Folder whose notes are all templates, wherever it is in the vault
+    #[arg(long, default_value = "Templates")]
+    template_folder: String,
+
+    /// Skip daily notes dated before this day, e.g. 2024-05-01
+    #[arg(long)]
+    since: Option<NaiveDate>,
+
+    /// Skip daily notes dated after this day
+    #[arg(long)]
+    until: Option<NaiveDate>,
+
+    /// Skip every note that isn't a daily note
+    #[arg(long)]
+    only_daily: bool,
+
+    /// chrono format of daily note names
+    #[arg(long, default_value = DAILY_FORMAT)]
+    daily_format: String,
+
+    /// Directory to cache parsed notes in; defaults to ~/.cache/darkstone
+    #[arg(long)]
+    cache_dir: Option<PathBuf>,
+
+    /// Parse every note without reading or writing the cache
+    #[arg(long)]
+    no_cache: bool,
+
+    /// Delete the vault's cache before scanning, so every note is parsed again
+    #[arg(long)]
+    clear_cache: bool,
+
+    /// Directory of .scm files replacing the built-in tree-sitter queries;
+    /// defaults to ~/.config/darkstone/queries
+    #[arg(long)]
+    query_dir: Option<PathBuf>,
+
+    /// When to color the output; auto colors it on a terminal unless
+    /// NO_COLOR is set
+    #[arg(long, value_enum, default_value_t = Color::Auto)]
+    color: Color,
+
+    /// Don't show progress or warnings, only the command's output
+    #[arg(short, long)]
+    quiet: bool,
+
+    /// Log what the scan does to stderr; -VV also logs every note. The short
+    /// flag is -V because -v is --vault-path
+    #[arg(short = 'V', long, action = ArgAction::Count, conflicts_with = "quiet")]
+    verbose: u8,
+
+    /// End each record of the targets, backlinks, orphans, filter,
+    /// templates and attachments --unused lists with a NUL byte instead of a
+    /// newline, for names with newlines in them; pair it with `xargs -0`
+    #[arg(short = '0', long)]
+    null: bool,
+
+    /// Print how long each phase of the run took to stderr at the end
+    #[arg(long)]
+    timing: bool,
+
+    /// Number of threads to parse with; defaults to one per core
+    #[arg(short, long)]
+    jobs: Option<usize>,
+
+    /// Read a single note from standard input instead of a vault, named
+    /// `stdin` unless given as --stdin=NAME
+    #[arg(
+        long,
+        value_name = "NAME",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "stdin"
+    )]
+    stdin: Option<String>,
+
+    /// Keep running and print the output again whenever a note changes
+    #[arg(short, long)]
+    watch: bool,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// List the notes that link to a note
+    Backlinks {
+        /// Note name or alias
+        note: String,
+    },
+    /// Report links that don't resolve to a note, exiting non-zero if any are found
+    Lint {
+        /// Also report notes linking to themselves
+        #[arg(long)]
+        self_links: bool,
+
+        /// Follow each broken link with the note names it was most likely
+        /// meant to be
+        #[arg(long)]
+        suggest: bool,
+    },
+    /// Run every check on the vault, exiting non-zero if a denied one finds
+    /// a problem. Broken links, broken anchors, missing embeds and
+    /// duplicate names are denied by default, alias collisions only warned
+    /// about, and self links and orphans not checked
+    Check {
+        /// Checks whose problems fail the run
+        #[arg(long, value_enum, value_delimiter = ',')]
+        deny: Vec<CheckKind>,
+
+        /// Checks whose problems are reported without failing the run
+        #[arg(long, value_enum, value_delimiter = ',')]
+        warn: Vec<CheckKind>,
+
+        /// Checks to skip; --warn wins over this and --deny over both
+        #[arg(long, value_enum, value_delimiter = ',')]
+        allow: Vec<CheckKind>,
+
+        #[arg(short, long, value_enum, default_value_t = Format::Text)]
+        format: Format,
+    },
+    /// List notes with no inbound or outbound links
+    Orphans {
+        /// Report notes without backlinks, even if they link elsewhere
+        #[arg(long)]
+        inbound_only: bool,
+    },
+    /// List every link in the vault
+    Links {
+        #[arg(short, long, value_enum, default_value_t = LinksFormat::Text)]
+        format: LinksFormat,
+
+        /// Leave out the CSV header row
+        #[arg(long)]
+        no_header: bool,
+    },
+    /// List every web URL linked from the vault, followed by the notes linking to it
+    Urls,
+    /// List links to images, PDFs and other files that aren't notes
+    Attachments {
+        /// Only list links to files that don't exist
+        #[arg(long, conflicts_with = "unused")]
+        missing: bool,
+
+        /// List files no note links to, instead of links
+        #[arg(long)]
+        unused: bool,
+    },
+    /// List every tag with the number of times it occurs
+    Tags {
+        #[arg(short, long, value_enum, default_value_t = Format::Text)]
+        format: Format,
+
+        /// Count the notes each tag is in, rather than its occurrences, and
+        /// list the most common first
+        #[arg(long, conflicts_with = "cooccurrence")]
+        count: bool,
+
+        /// Count notes with a nested tag like `project/active` towards
+        /// `project` too
+        #[arg(long, requires = "count")]
+        rollup: bool,
+
+        /// List pairs of tags with the number of notes they are both in,
+        /// most common first
+        #[arg(long)]
+        cooccurrence: bool,
+
+        /// Leave out pairs in fewer notes than this
+        #[arg(long, default_value_t = 1, requires = "cooccurrence")]
+        min: usize,
+    },
+    /// Rename a note and update every link to it
+    Rename {
+        /// Note name or alias
+        note: String,
+
+        /// New name for the note, without folders or extension
+        new_name: String,
+
+        /// Print the changes without making them
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Print a note's headings as an indented outline
+    Outline {
+        /// Note name or alias
+        note: String,
+    },
+    /// List open tasks with the note and line they are on, then a count of
+    /// open and done tasks
+    Tasks,
+    /// Run a tree-sitter query over every note and print what it captures
+    Query {
+        /// The query, in tree-sitter's S-expression syntax
+        query: String,
+
+        /// Only print nodes captured under this name
+        #[arg(short, long)]
+        capture: Option<String>,
+
+        /// Tree to run the query over: markdown blocks, the inline text
+        /// within them, or YAML frontmatter
+        #[arg(short, long, value_enum, default_value_t = QueryGrammar::Markdown)]
+        grammar: QueryGrammar,
+    },
+    /// List callouts with the note and line they are on
+    Callouts {
+        /// Only list callouts of this type, e.g. warning
+        #[arg(short, long)]
+        kind: Option<String>,
+    },
+    /// List notes by the number of words in them, then the total
+    Wordcount {
+        #[arg(short, long, value_enum, default_value_t = Order::Desc)]
+        sort: Order,
+
+        /// Reading speed, in words per minute, for the reading time estimate
+        #[arg(long, default_value_t = 200)]
+        wpm: usize,
+    },
+    /// List the notes matching every condition given
+    Filter {
+        /// Frontmatter or inline `key=value` field the note must have; may be
+        /// repeated
+        #[arg(long = "where", value_parser = parse_predicate)]
+        predicates: Vec<(String, String)>,
+
+        /// Tag the note must have; may be repeated
+        #[arg(long = "tag")]
+        tags: Vec<String>,
+    },
+    /// List the notes with the most backlinks
+    Popular {
+        /// Number of notes to show
+        #[arg(short, long, default_value_t = 10)]
+        top: usize,
+    },
+    /// List notes by PageRank, most important first
+    Rank {
+        /// Probability of following a link rather than jumping to a random note
+        #[arg(short, long, default_value_t = 0.85)]
+        damping: f64,
+
+        #[arg(short, long, default_value_t = 50)]
+        iterations: usize,
+    },
+    /// List groups of notes linked to each other, largest first
+    Components,
+    /// List the notes within a number of links of a note, nearest first
+    Neighbors {
+        /// Note name or alias
+        note: String,
+
+        /// Number of links to follow
+        #[arg(short, long, default_value_t = 1)]
+        depth: usize,
+
+        /// Only follow links out of each note, or only links into it,
+        /// rather than both
+        #[arg(long, value_enum)]
+        directed: Option<Directed>,
+    },
+    /// List chains of links that lead back to the note they started from
+    Cycles {
+        /// Longest cycle to look for, in notes
+        #[arg(long, default_value_t = 4)]
+        max: usize,
+    },
+    /// List note names used by more than one file
+    Duplicates,
+    /// List the template notes left out of every other command
+    Templates,
+    /// List every alias with the note it stands for, warning about aliases
+    /// more than one note claims
+    Aliases,
+    /// Summarize the notes and links in the vault
+    Stats {
+        #[arg(short, long, value_enum, default_value_t = Format::Text)]
+        format: Format,
+
+        /// Count notes, links and orphans for each folder at the top of the
+        /// vault instead
+        #[arg(long)]
+        by_folder: bool,
+    },
+    /// Write the vault's notes, links, aliases and tags to a database
+    Export {
+        #[arg(value_enum)]
+        format: ExportFormat,
+
+        /// File to write to; existing tables are replaced
+        #[arg(short, long)]
+        out: PathBuf,
+    },
+    /// Print the link graph
+    Graph {
+        #[arg(short, long, value_enum, default_value_t = GraphFormat::Dot)]
+        format: GraphFormat,
+
+        /// Add a node for each link destination that doesn't resolve, linked
+        /// from the notes referring to it
+        #[arg(long = "include-broken-as-nodes", alias = "include-broken")]
+        include_broken: bool,
+    },
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum LinksFormat {
+    Text,
+    Csv,
+    Jsonl,
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum Order {
+    Asc,
+    Desc,
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum Directed {
+    Out,
+    In,
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum SortOrder {
+    Byte,
+    Natural,
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum QueryGrammar {
+    Markdown,
+    Inline,
+    Yaml,
+}
+
+impl From<QueryGrammar> for Grammar {
+    fn from(grammar: QueryGrammar) -> Self {
+        match grammar {
+            QueryGrammar::Markdown => Grammar::Markdown,
+            QueryGrammar::Inline => Grammar::Inline,
+            QueryGrammar::Yaml => Grammar::Yaml,
+        }
+    }
+}
+
+/// Something `check` looks for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, ValueEnum, Serialize)]
+#[serde(rename_all = "kebab-case")]
+enum CheckKind {
+    /// Links to a note or file that doesn't exist
+    BrokenLinks,
+    /// Links to a heading or block the note doesn't have
+    BrokenAnchors,
+    /// Embeds of a note or file that doesn't exist
+    MissingEmbeds,
+    /// Note names used by more than one file
+    Duplicates,
+    /// Aliases more than one note claims
+    AliasCollisions,
+    /// Notes linking to themselves
+    SelfLinks,
+    /// Notes with no links in or out
+    Orphans,
+}
+
+impl CheckKind {
+    fn default_severity(self) -> Severity {
+        match self {
+            CheckKind::BrokenLinks
+            | CheckKind::BrokenAnchors
+            | CheckKind::MissingEmbeds
+            | CheckKind::Duplicates => Severity::Deny,
+            CheckKind::AliasCollisions => Severity::Warn,
+            CheckKind::SelfLinks | CheckKind::Orphans => Severity::Allow,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum Severity {
+    Allow,
+    Warn,
+    Deny,
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum ExportFormat {
+    Sqlite,
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum GraphFormat {
+    Dot,
+    D3Json,
+    Graphml,
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum Color {
+    Auto,
+    Always,
+    Never,
+}
+
+// Styles for the text reports. anstream strips them again when colors are
+// off, and JSON, CSV and graph output never uses them.
+const BROKEN: Style = AnsiColor::Red.on_default();
+const SOURCE: Style = Style::new().dimmed();
+const COUNT: Style = Style::new().bold();
+const ERROR: Style = AnsiColor::Red.on_default().bold();
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum Format {
+    Text,
+    Json,
+    /// One JSON object per line, each with a `type` field
+    Jsonl,
+}
+
+/// `Format`, plus the link event stream, which only makes sense in place of
+/// the target list.
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum OutputFormat {
+    Text,
+    Json,
+    /// One JSON object per line, each with a `type` field
+    Jsonl,
+    /// Instead of the target list, every link with where it leads, one JSON
+    /// object per line after a versioned `meta` record; with --watch, notes
+    /// are written again as their links change
+    Ndjson,
+}
+
+impl OutputFormat {
+    /// The format to print the target list in, or `None` for the event
+    /// stream.
+    fn targets(self) -> Option<Format> {
+        match self {
+            OutputFormat::Text => Some(Format::Text),
+            OutputFormat::Json => Some(Format::Json),
+            OutputFormat::Jsonl => Some(Format::Jsonl),
+            OutputFormat::Ndjson => None,
+        }
+    }
+}
+
+/// A line of JSON Lines output: `item`'s fields, plus what kind of item it
+/// is.
+#[derive(Serialize)]
+struct Record<T> {
+    #[serde(rename = "type")]
+    kind: &'static str,
+    #[serde(flatten)]
+    item: T,
+}
+
+#[derive(Serialize)]
+struct JsonLink<'a> {
+    source: String,
+    line: usize,
+    kind: LinkKind,
+    destination: &'a str,
+    text: &'a str,
+    raw: &'a str,
+    is_embed: bool,
+    heading: Option<&'a str>,
+    block: Option<&'a str>,
+}
+
+/// One problem `check` found. `path` is as the note was loaded, so relative
+/// to the working directory when the vault path is.
+#[derive(Serialize)]
+struct Problem {
+    check: CheckKind,
+    severity: Severity,
+    path: String,
+    line: Option<usize>,
+    message: String,
+}
+
+#[derive(Serialize)]
+struct CheckSummary {
+    errors: usize,
+    warnings: usize,
+}
+
+#[derive(Serialize)]
+struct CheckReport<'a> {
+    problems: &'a [Problem],
+    #[serde(flatten)]
+    summary: &'a CheckSummary,
+}
+
+#[derive(Serialize)]
+struct JsonTag<'a> {
+    tag: &'a str,
+    count: usize,
+}
+
+#[derive(Serialize)]
+struct JsonTagPair<'a> {
+    tags: [&'a str; 2],
+    count: usize,
+}
+
+#[derive(Serialize)]
+struct JsonTarget<'a> {
+    note: &'a str,
+    alias: Option<&'a str>,
+}
+
+impl<'a> From<&'a Target> for JsonTarget<'a> {
+    fn from(target: &'a Target) -> Self {
+        Self {
+            note: target.note(),
+            alias: target.alias(),
+        }
+    }
+}
+
+fn main() {
+    let matches = Args::command().get_matches();
+    let args = match Args::from_arg_matches(&matches) {
+        Ok(args) => args,
+        Err(err) => err.exit(),
+    };
+
+    match start(args, &matches) {
+        Ok(true) => {}
+        Ok(false) => std::process::exit(1),
+        // Output piped into `head` and the like stops being read early.
+        Err(err) if is_broken_pipe(err.as_ref()) => {}
+        Err(err) => {
+            eprintln!("{ERROR}error:{ERROR:#} {}", err);
+            std::process::exit(2);
+        }
+    }
+}
+
+/// Defaults read from a `darkstone.toml`, for flags not given on the command
+/// line.
+#[derive(Debug, Default, Deserialize)]
+struct Config {
+    extensions: Option<Vec<String>>,
+    exclude: Option<Vec<String>>,
+    /// Used by every command whose output comes in a format of that name.
+    format: Option<String>,
+    templates_dir: Option<String>,
+    jobs: Option<usize>,
+}
+
+impl Config {
+    const FILE: &'static str = "darkstone.toml";
+    const KEYS: [&'static str; 5] = ["extensions", "exclude", "format", "templates_dir", "jobs"];
+
+    /// The file `--config` names, or failing that the one in the first
+    /// vault, if it has one.
+    fn find(args: &Args) -> Result<Option<Self>, String> {
+        let path = match &args.config {
+            Some(path) => path.clone(),
+            None => {
+                let vault = args
+                    .vault_path
+                    .first()
+                    .map_or(Path::new("."), |p| p.as_path());
+                let path = vault.join(Self::FILE);
+                match path.is_file() {
+                    true => path,
+                    false => return Ok(None),
+                }
+            }
+        };
+
+        let text = std::fs::read_to_string(&path)
+            .map_err(|err| format!("can't read config file '{}': {}", path.display(), err))?;
+        let table = text
+            .parse::<toml::Table>()
+            .map_err(|err| format!("config file '{}': {}", path.display(), err))?;
+
+        // Keys a later version knows about shouldn't stop this one.
+        for key in table.keys().filter(|k| !Self::KEYS.contains(&k.as_str())) {
+            warn!("unknown key `{}` in {}", key, path.display());
+        }
+
+        toml::Value::Table(table)
+            .try_into()
+            .map(Some)
+            .map_err(|err| format!("config file '{}': {}", path.display(), err))
+    }
+
+    /// Fills in whatever `matches` shows wasn't given on the command line.
+    fn apply(self, args: &mut Args, matches: &ArgMatches) -> Result<(), String> {
+        if let (Some(extensions), false) = (self.extensions, given(matches, "extensions")) {
+            args.extensions = extensions;
+        }
+        if let (Some(exclude), false) = (self.exclude, given(matches, "exclude")) {
+            args.exclude = exclude
+                .iter()
+                .map(|p| glob::Pattern::new(p))
+                .collect::<Result<_, _>>()
+                .map_err(|err| format!("config file exclude: {}", err))?;
+        }
+        if let (Some(folder), false) = (self.templates_dir, given(matches, "template_folder")) {
+            args.template_folder = folder;
+        }
+        if args.jobs.is_none() {
+            args.jobs = self.jobs;
+        }
+
+        let format = match self.format {
+            Some(format) => format,
+            None => return Ok(()),
+        };
+        if let (Ok(value), false) = (
+            OutputFormat::from_str(&format, true),
+            given(matches, "format"),
+        ) {
+            args.format = value;
+        }
+        format_command(args, matches, &format);
+
+        Ok(())
+    }
+}
+
+/// Whether `id` was given on the command line, rather than left to its
+/// default or the environment.
+fn given(matches: &ArgMatches, id: &str) -> bool {
+    matches.value_source(id) == Some(clap::parser::ValueSource::CommandLine)
+}
+
+/// Sets the command's own --format to `format`, unless it was given one or
+/// has no such format.
+fn format_command(args: &mut Args, matches: &ArgMatches, format: &str) {
+    let sub = match matches.subcommand() {
+        Some((_, sub)) => sub,
+        None => return,
+    };
+    match &mut args.command {
+        Some(Command::Links { format: f, .. }) if !given(sub, "format") => {
+            if let Ok(value) = LinksFormat::from_str(format, true) {
+                *f = value;
+            }
+        }
+        Some(
+            Command::Tags { format: f, .. }
+            | Command::Check { format: f, .. }
+            | Command::Stats { format: f, .. },
+        ) if !given(sub, "format") => {
+            if let Ok(value) = Format::from_str(format, true) {
+                *f = value;
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Whether `err` comes from writing to a pipe that was closed, even when
+/// wrapped in a CSV or JSON error.
+fn is_broken_pipe(err: &(dyn std::error::Error + 'static)) -> bool {
+    std::iter::successors(Some(err), |e| e.source()).any(|e| {
+        let io = match e.downcast_ref::<csv::Error>().map(|e| e.kind()) {
+            Some(csv::ErrorKind::Io(io)) => Some(io),
+            _ => e.downcast_ref::<std::io::Error>(),
+        };
+
+        io.is_some_and(|e| e.kind() == std::io::ErrorKind::BrokenPipe)
+    })
+}
+
+/// Loads the vault and runs the command, returning whether it passed.
+fn start(mut args: Args, matches: &ArgMatches) -> Result<bool, Box<dyn std::error::Error>> {
+    let started = Instant::now();
+    match args.color {
+        Color::Auto => anstream::ColorChoice::Auto,
+        Color::Always => anstream::ColorChoice::Always,
+        Color::Never => anstream::ColorChoice::Never,
+    }
+    .write_global();
+
+    let level = match (args.quiet, args.verbose) {
+        (true, _) => Level::ERROR,
+        (false, 0) => Level::WARN,
+        (false, 1) => Level::INFO,
+        (false, 2) => Level::DEBUG,
+        (false, _) => Level::TRACE,
+    };
+    tracing_subscriber::fmt()
+        .with_max_level(level)
+        .with_writer(anstream::stderr)
+        .with_target(false)
+        .without_time()
+        .init();
+
+    if let Some(config) = Config::find(&args)? {
+        config.apply(&mut args, matches)?;
+    }
+    // `darkstone -f jsonl tags` means the same as `darkstone tags -f jsonl`.
+    if given(matches, "format") {
+        if let Some(format) = args.format.to_possible_value() {
+            format_command(&mut args, matches, format.get_name());
+        }
+    }
+    let args = &args;
+
+    if let Some(jobs) = args.jobs {
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(jobs)
+            .build_global()?;
+    }
+    let mut options = ScanOptions {
+        extensions: args.extensions.clone(),
+        respect_ignore: !args.no_ignore,
+        max_depth: match args.max_depth {
+            0 => None,
+            depth => Some(depth),
+        },
+        lossy: args.lossy,
+        include_comments: args.include_comments,
+        include_templates: args.include_templates,
+        template_folder: args.template_folder.clone(),
+        // Only these look past the links, aliases and tags of a note.
+        summarize: !matches!(
+            args.command,
+            Some(
+                Command::Query { .. }
+                    | Command::Callouts { .. }
+                    | Command::Wordcount { .. }
+                    | Command::Filter { .. }
+                    | Command::Tasks
             )
-            .unwrap(),
-        });
+        ),
+        dates: DateRange {
+            format: args.daily_format.clone(),
+            since: args.since,
+            until: args.until,
+            only_daily: args.only_daily,
+        },
+        ..ScanOptions::default()
+    };
+    options.exclude.extend(args.exclude.clone());
+    if !args.quiet {
+        // Stays hidden unless stderr is a terminal.
+        options.progress = ProgressBar::new_spinner().with_style(ProgressStyle::with_template(
+            "{spinner} {pos} notes ({per_sec})",
+        )?);
+        options
+            .progress
+            .enable_steady_tick(Duration::from_millis(100));
+    }
+    if !args.no_cache {
+        options.cache_dir = args.cache_dir.clone().or_else(default_cache_dir);
+    }
+    let vault_paths = match args.vault_path.is_empty() {
+        true => vec![PathBuf::from(".")],
+        false => args.vault_path.clone(),
+    };
+    if let (true, Some(dir)) = (args.clear_cache, &options.cache_dir) {
+        for path in &vault_paths {
+            Vault::clear_cache(path, dir)?;
+        }
+    }
+    let query_cache = match args.query_dir.clone().or_else(default_query_dir) {
+        Some(dir) => QueryCache::from_dir(&dir)?,
+        None => QueryCache::new()?,
+    };
+    let query_cache = Arc::new(query_cache);
 
-        let glob = format!("{}/**/*.md", path.as_os_str().to_str().unwrap());
-        let notes = glob::glob(&glob)
-            .unwrap()
-            .into_iter()
-            .par_bridge()
-            .collect::<Result<Vec<PathBuf>, glob::GlobError>>()
-            .unwrap()
-            .into_iter()
-            .par_bridge()
-            .map(|p| Note::new(p, query_cache.clone()))
-            .collect();
+    // A single note has no vault around it for its links to resolve in.
+    let note = match (&args.stdin, vault_paths.as_slice()) {
+        (Some(name), _) => {
+            let mut content = String::new();
+            std::io::stdin().read_to_string(&mut content)?;
+            Some(Note::from_str(name, &content, query_cache.clone())?)
+        }
+        (None, [path]) if path.is_file() && is_note_file(path, &args.extensions) => {
+            match args.lossy {
+                true => Some(Note::new_lossy(path.clone(), query_cache.clone())?),
+                false => Some(Note::new(path.clone(), query_cache.clone())?),
+            }
+        }
+        _ => None,
+    };
+    if note.is_some() && (args.watch || needs_vault(&args.command)) {
+        return Err("this command needs a vault, not a single note".into());
+    }
+
+    let vault = match note {
+        Some(note) => Vault::from_notes(vec![note.with_comments(args.include_comments)]),
+        None => Vault::with_roots(vault_paths, &options, query_cache),
+    };
+    options.progress.finish_and_clear();
+    let mut vault = vault?;
 
-        Self { notes }
+    if args.vault_path.is_empty() && vault.notes().is_empty() && vault.templates().is_empty() {
+        warn!(
+            "no notes found in the current directory; pass --vault-path to read a vault elsewhere"
+        );
     }
 
-    fn notes(&self) -> Vec<Note> {
-        self.notes.clone()
+    let command_started = Instant::now();
+    let ok = run(&vault, args)?;
+    if args.timing {
+        print_timings(
+            vault.timings(),
+            command_started.elapsed(),
+            started.elapsed(),
+        );
     }
 
-    fn links(&self) -> Vec<Link> {
-        self.notes().iter().map(|n| n.links()).flatten().collect()
+    if args.watch {
+        watch(&mut vault, args)?;
     }
 
-    fn targets(&self) -> Vec<String> {
-        self.notes()
-            .par_iter()
-            .map(|n| n.targets())
-            .flatten()
-            .collect::<Vec<String>>()
-            .into_iter()
-            .unique()
-            .collect()
+    Ok(ok)
+}
+
+/// Phase timings, in milliseconds so runs are easy to compare. The command
+/// phase covers resolving links and writing the output, along with
+/// extracting anything the scan didn't.
+fn print_timings(timings: Timings, command: Duration, total: Duration) {
+    let phases = [
+        ("walk", timings.walk),
+        ("parse", timings.parse),
+        ("extract", timings.extract),
+        ("command", command),
+        ("total", total),
+    ];
+
+    for (phase, duration) in phases {
+        eprintln!("{:<8}{:>10.3} ms", phase, duration.as_secs_f64() * 1000.0);
     }
 }
 
-#[derive(Clone, Debug)]
-struct Note {
-    path: PathBuf,
-    content: String,
-    tree: MarkdownTree,
-    query_cache: Arc<QueryCache>,
+/// Whether `path` has one of the extensions notes are loaded from.
+fn is_note_file(path: &Path, extensions: &[String]) -> bool {
+    let extension = path.extension().and_then(|e| e.to_str());
+
+    extensions
+        .iter()
+        .any(|e| extension.is_some_and(|ext| e.trim_start_matches('.').eq_ignore_ascii_case(ext)))
 }
 
-impl Note {
-    fn new(path: PathBuf, query_cache: Arc<QueryCache>) -> Self {
-        let content = std::fs::read_to_string(&path).unwrap();
-        let mut parser = MarkdownParser::default();
-        let tree = parser.parse(content.as_bytes(), None).unwrap();
+/// Whether `command` looks at how notes link to each other, which means
+/// nothing for a single note.
+fn needs_vault(command: &Option<Command>) -> bool {
+    matches!(
+        command,
+        Some(
+            Command::Backlinks { .. }
+                | Command::Lint { .. }
+                | Command::Check { .. }
+                | Command::Orphans { .. }
+                | Command::Attachments { .. }
+                | Command::Rename { .. }
+                | Command::Popular { .. }
+                | Command::Rank { .. }
+                | Command::Components
+                | Command::Neighbors { .. }
+                | Command::Cycles { .. }
+                | Command::Duplicates
+                | Command::Templates
+                | Command::Graph { .. }
+        )
+    )
+}
 
-        Self {
-            path,
-            content,
-            tree,
-            query_cache,
+/// `$XDG_CACHE_HOME/darkstone`, falling back to `~/.cache/darkstone`.
+fn default_cache_dir() -> Option<PathBuf> {
+    let cache = match std::env::var_os("XDG_CACHE_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(std::env::var_os("HOME")?).join(".cache"),
+    };
+
+    Some(cache.join("darkstone"))
+}
+
+/// `$XDG_CONFIG_HOME/darkstone/queries`, falling back to
+/// `~/.config/darkstone/queries`.
+fn default_query_dir() -> Option<PathBuf> {
+    let config = match std::env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(std::env::var_os("HOME")?).join(".config"),
+    };
+
+    Some(config.join("darkstone").join("queries"))
+}
+
+/// Prints the output of the selected command, returning false if the command
+/// found problems that should fail the run.
+fn run(vault: &Vault, args: &Args) -> Result<bool, Box<dyn std::error::Error>> {
+    for err in vault.errors() {
+        warn!("skipping {}", err);
+    }
+    for note in vault.notes().iter().filter(|n| n.is_lossy()) {
+        warn!(
+            "{}: invalid UTF-8 replaced",
+            vault.relative_path(note.path()).display()
+        );
+    }
+
+    match &args.command {
+        None => match args.format.targets() {
+            Some(format) => {
+                print_targets(vault, format, args.keep_extension, args.sort, args.null)?
+            }
+            None => Events::new(vault).write_all()?,
+        },
+        Some(Command::Backlinks { note }) => print_backlinks(vault, note, args.null),
+        Some(Command::Lint {
+            self_links,
+            suggest,
+        }) => return Ok(lint(vault, *self_links, *suggest)),
+        Some(Command::Check {
+            deny,
+            warn,
+            allow,
+            format,
+        }) => return Ok(check(vault, deny, warn, allow, *format)?),
+        Some(Command::Orphans { inbound_only }) => print_orphans(vault, *inbound_only, args.null),
+        Some(Command::Links { format, no_header }) => print_links(vault, *format, !no_header)?,
+        Some(Command::Urls) => print_urls(vault),
+        Some(Command::Attachments { missing, unused }) => {
+            print_attachments(vault, *missing, *unused, args.null)
+        }
+        Some(Command::Tags {
+            format,
+            count,
+            rollup,
+            cooccurrence: false,
+            ..
+        }) => match count {
+            true => print_tags(&vault.tag_note_counts(*rollup), *format, true)?,
+            false => print_tags(&vault.tag_counts(), *format, false)?,
+        },
+        Some(Command::Tags {
+            format,
+            cooccurrence: true,
+            min,
+            ..
+        }) => print_tag_pairs(vault, *format, *min)?,
+        Some(Command::Rename {
+            note,
+            new_name,
+            dry_run,
+        }) => rename(vault, note, new_name, *dry_run)?,
+        Some(Command::Outline { note }) => print_outline(vault, note)?,
+        Some(Command::Tasks) => print_tasks(vault),
+        Some(Command::Query {
+            query,
+            capture,
+            grammar,
+        }) => print_query(vault, query, capture.as_deref(), *grammar)?,
+        Some(Command::Callouts { kind }) => print_callouts(vault, kind.as_deref()),
+        Some(Command::Wordcount { sort, wpm }) => print_wordcount(vault, *sort, *wpm),
+        Some(Command::Filter { predicates, tags }) => {
+            print_filter(vault, predicates, tags, args.null)
+        }
+        Some(Command::Popular { top }) => print_popular(vault, *top),
+        Some(Command::Rank {
+            damping,
+            iterations,
+        }) => print_rank(vault, *damping, *iterations),
+        Some(Command::Components) => print_components(vault),
+        Some(Command::Neighbors {
+            note,
+            depth,
+            directed,
+        }) => print_neighbors(vault, note, *depth, *directed),
+        Some(Command::Cycles { max }) => print_cycles(vault, *max),
+        Some(Command::Duplicates) => print_duplicates(vault),
+        Some(Command::Templates) => print_templates(vault, args.null),
+        Some(Command::Aliases) => print_aliases(vault),
+        Some(Command::Stats {
+            format,
+            by_folder: false,
+        }) => print_stats(vault, *format)?,
+        Some(Command::Stats {
+            format,
+            by_folder: true,
+        }) => print_folder_stats(vault, *format)?,
+        Some(Command::Export { format, out }) => match format {
+            ExportFormat::Sqlite => export::sqlite(vault, out)?,
+        },
+        Some(Command::Graph {
+            format,
+            include_broken,
+        }) => print_graph(vault, *format, *include_broken)?,
+    }
+
+    Ok(true)
+}
+
+/// Whether the command writes text meant for people, rather than CSV, JSON,
+/// a graph or a file.
+fn prints_text(args: &Args) -> bool {
+    match &args.command {
+        None => matches!(args.format, OutputFormat::Text),
+        Some(Command::Links { format, .. }) => matches!(format, LinksFormat::Text),
+        Some(
+            Command::Check { format, .. }
+            | Command::Tags { format, .. }
+            | Command::Stats { format, .. },
+        ) => matches!(format, Format::Text),
+        Some(Command::Export { .. } | Command::Graph { .. }) => false,
+        Some(_) => true,
+    }
+}
+
+/// Reloads changed notes and reruns the command until interrupted.
+fn watch(vault: &mut Vault, args: &Args) -> Result<(), Box<dyn std::error::Error>> {
+    let (sender, receiver) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(sender)?;
+    for root in vault.roots() {
+        watcher.watch(root, RecursiveMode::Recursive)?;
+    }
+
+    // The event stream only writes what changed, rather than starting over.
+    let mut events = match (&args.command, args.format) {
+        (None, OutputFormat::Ndjson) => Some(Events::new(vault)),
+        _ => None,
+    };
+
+    while let Ok(event) = receiver.recv() {
+        let mut paths = changed_paths(event);
+
+        // Editors often write a file several times when saving, so wait for
+        // the events to settle before doing any work.
+        while let Ok(event) = receiver.recv_timeout(Duration::from_millis(200)) {
+            paths.append(&mut changed_paths(event));
+        }
+
+        let mut changed = false;
+        for path in paths.iter().unique() {
+            changed |= vault.reload(path);
+        }
+
+        match (changed, &mut events) {
+            (false, _) => {}
+            (true, Some(events)) => events.update(vault)?,
+            (true, None) => {
+                // Clearing the screen only helps someone reading along, and
+                // would end up as junk in a file or another program's input.
+                if std::io::stdout().is_terminal() && prints_text(args) {
+                    std::print!("\x1B[2J\x1B[H");
+                }
+                run(vault, args)?;
+            }
+        }
+        std::io::stdout().flush()?;
+    }
+
+    Ok(())
+}
+
+fn changed_paths(event: notify::Result<Event>) -> Vec<PathBuf> {
+    match event {
+        Ok(event) => match event.kind {
+            EventKind::Create(_) | EventKind::Modify(_) | EventKind::Remove(_) => event.paths,
+            _ => vec![],
+        },
+        Err(_) => vec![],
+    }
+}
+
+fn print_targets(
+    vault: &Vault,
+    format: Format,
+    raw: bool,
+    sort: SortOrder,
+    null: bool,
+) -> std::io::Result<()> {
+    let mut targets = match raw {
+        true => vault.raw_targets(),
+        false => vault.targets(),
+    };
+    // Sorted as printed, so aliases sit beside the names they share a
+    // prefix with.
+    match sort {
+        SortOrder::Byte => targets.sort_by_cached_key(|t| t.to_string()),
+        SortOrder::Natural => targets.sort_by(|a, b| natural_cmp(&a.to_string(), &b.to_string())),
+    }
+
+    match format {
+        Format::Text => {
+            for target in targets {
+                print_record(target, null);
+            }
+        }
+        Format::Json => {
+            let targets = targets.iter().map(JsonTarget::from).collect::<Vec<_>>();
+            println!("{}", serde_json::to_string(&targets)?);
+        }
+        Format::Jsonl => {
+            for target in &targets {
+                write_record("target", JsonTarget::from(target))?;
+            }
         }
     }
 
-    fn targets(&self) -> Vec<String> {
-        let mut targets = vec![self.name()];
-        targets.append(&mut self.alias_targets());
+    Ok(())
+}
 
-        let mut destinations = self.links().iter().map(|l| l.destination.clone()).collect();
-        targets.append(&mut destinations);
+fn print_backlinks(vault: &Vault, note: &str, null: bool) {
+    let mut paths = vault
+        .backlinks(note)
+        .iter()
+        .map(|n| vault.relative_path(n.path()))
+        .collect::<Vec<_>>();
+    paths.sort();
 
-        targets
+    for path in paths {
+        print_record(path.display(), null);
     }
+}
 
-    fn name(&self) -> String {
-        self.path
-            .file_stem()
-            .unwrap()
-            .to_owned()
-            .to_str()
-            .unwrap()
-            .to_string()
+fn lint(vault: &Vault, self_links: bool, suggest: bool) -> bool {
+    let index = vault.index();
+    let mut broken = index.broken_links();
+    broken.sort_by(|a, b| {
+        a.source
+            .cmp(&b.source)
+            .then_with(|| a.destination.cmp(&b.destination))
+    });
+
+    for link in &broken {
+        let suggestions = match suggest {
+            true => vault.suggestions(&link.destination),
+            false => vec![],
+        };
+        let hint = match suggestions.is_empty() {
+            true => String::new(),
+            false => format!(
+                " (did you mean {}?)",
+                suggestions
+                    .iter()
+                    .map(|s| format!("\"{}\"", s))
+                    .join(" or ")
+            ),
+        };
+        println!(
+            "{SOURCE}{}{SOURCE:#} -> {BROKEN}{}{BROKEN:#}{}",
+            vault.relative_path(&link.source).display(),
+            link.destination,
+            hint
+        );
+    }
+
+    let mut anchors = index.broken_anchors();
+    anchors.sort_by(|a, b| {
+        a.source
+            .cmp(&b.source)
+            .then_with(|| a.raw_destination.cmp(&b.raw_destination))
+    });
+
+    for link in &anchors {
+        let problem = match link.block {
+            Some(_) => "no such block",
+            None => "no such heading",
+        };
+        println!(
+            "{SOURCE}{}{SOURCE:#} -> {BROKEN}{}{BROKEN:#} ({})",
+            vault.relative_path(&link.source).display(),
+            link.raw_destination,
+            problem
+        );
     }
 
-    fn alias_targets(&self) -> Vec<String> {
-        let name = self.name();
+    let mut own = match self_links {
+        true => index.self_links(),
+        false => vec![],
+    };
+    own.sort_by(|a, b| {
+        a.source
+            .cmp(&b.source)
+            .then_with(|| a.destination.cmp(&b.destination))
+    });
 
-        self.aliases()
-            .par_iter()
-            .map(|alias| format!("{}|{}", name, alias))
-            .collect()
+    for link in &own {
+        println!(
+            "{SOURCE}{}{SOURCE:#} -> {BROKEN}{}{BROKEN:#} (self link)",
+            vault.relative_path(&link.source).display(),
+            link.destination
+        );
     }
 
-    fn aliases(&self) -> Vec<String> {
-        match self.parsed_frontmatter() {
-            Some(frontmatter) => {
-                let mut aliases = vec![];
-                // dbg!(frontmatter.root_node().to_sexp());
-                // dbg!(&alias_query);
-                let mut query_cursor = QueryCursor::new();
-                let frontmatter_content = self.frontmatter().unwrap().clone();
-                let matches = query_cursor.matches(
-                    &self.query_cache.alias_query,
-                    frontmatter.root_node().clone(),
-                    frontmatter_content.as_bytes(),
+    broken.is_empty() && anchors.is_empty() && own.is_empty()
+}
+
+fn check(
+    vault: &Vault,
+    deny: &[CheckKind],
+    warn: &[CheckKind],
+    allow: &[CheckKind],
+    format: Format,
+) -> std::io::Result<bool> {
+    let index = vault.index();
+    let severity = |kind: CheckKind| match kind {
+        _ if deny.contains(&kind) => Severity::Deny,
+        _ if warn.contains(&kind) => Severity::Warn,
+        _ if allow.contains(&kind) => Severity::Allow,
+        _ => kind.default_severity(),
+    };
+
+    let mut problems = vec![];
+    let mut report = |kind: CheckKind, path: &Path, line: Option<usize>, message: String| {
+        let severity = severity(kind);
+        if severity != Severity::Allow {
+            problems.push(Problem {
+                check: kind,
+                severity,
+                path: path.display().to_string(),
+                line,
+                message,
+            });
+        }
+    };
+
+    for link in index.broken_links() {
+        let (kind, message) = match link.is_embed {
+            true => (
+                CheckKind::MissingEmbeds,
+                format!("embedded '{}' doesn't exist", link.destination),
+            ),
+            false => (
+                CheckKind::BrokenLinks,
+                format!("no note or file '{}'", link.destination),
+            ),
+        };
+        report(kind, &link.source, Some(link.start.line + 1), message);
+    }
+
+    for link in index.broken_anchors() {
+        let problem = match link.block {
+            Some(_) => "no such block",
+            None => "no such heading",
+        };
+        let message = format!("{} '{}'", problem, link.raw_destination);
+        report(
+            CheckKind::BrokenAnchors,
+            &link.source,
+            Some(link.start.line + 1),
+            message,
+        );
+    }
+
+    for (name, paths) in vault.duplicate_names() {
+        for path in &paths {
+            let message = format!("name '{}' is used by {} files", name, paths.len());
+            report(CheckKind::Duplicates, path, None, message);
+        }
+    }
+
+    for (alias, paths) in vault.alias_collisions() {
+        for path in &paths {
+            let message = format!("alias '{}' is claimed by {} notes", alias, paths.len());
+            report(CheckKind::AliasCollisions, path, None, message);
+        }
+    }
+
+    if severity(CheckKind::SelfLinks) != Severity::Allow {
+        for link in index.self_links() {
+            let message = format!("links to itself as '{}'", link.destination);
+            report(
+                CheckKind::SelfLinks,
+                &link.source,
+                Some(link.start.line + 1),
+                message,
+            );
+        }
+    }
+
+    if severity(CheckKind::Orphans) != Severity::Allow {
+        for note in index.orphans() {
+            report(
+                CheckKind::Orphans,
+                note.path(),
+                None,
+                "no links in or out".to_string(),
+            );
+        }
+    }
+
+    problems.sort_by(|a, b| {
+        natural_cmp(&a.path, &b.path)
+            .then(a.line.cmp(&b.line))
+            .then(a.message.cmp(&b.message))
+    });
+    let summary = CheckSummary {
+        errors: problems
+            .iter()
+            .filter(|p| p.severity == Severity::Deny)
+            .count(),
+        warnings: problems
+            .iter()
+            .filter(|p| p.severity == Severity::Warn)
+            .count(),
+    };
+
+    match format {
+        Format::Text => {
+            for problem in &problems {
+                let location = match problem.line {
+                    Some(line) => format!("{}:{}", problem.path, line),
+                    None => problem.path.clone(),
+                };
+                let severity = match problem.severity {
+                    Severity::Deny => format!("{BROKEN}error{BROKEN:#}"),
+                    _ => "warning".to_string(),
+                };
+                let check = problem.check.to_possible_value().unwrap();
+                println!(
+                    "{SOURCE}{}{SOURCE:#}: {}: {} [{}]",
+                    location,
+                    severity,
+                    problem.message,
+                    check.get_name()
                 );
+            }
+            println!(
+                "{COUNT}{}{COUNT:#} errors, {COUNT}{}{COUNT:#} warnings",
+                summary.errors, summary.warnings
+            );
+        }
+        Format::Json => {
+            let report = CheckReport {
+                problems: &problems,
+                summary: &summary,
+            };
+            println!("{}", serde_json::to_string(&report)?);
+        }
+        Format::Jsonl => {
+            for problem in &problems {
+                write_record("problem", problem)?;
+            }
+            write_record("summary", &summary)?;
+        }
+    }
+
+    Ok(summary.errors == 0)
+}
+
+fn print_orphans(vault: &Vault, inbound_only: bool, null: bool) {
+    let index = vault.index();
+    let orphans = match inbound_only {
+        true => index.unreferenced(),
+        false => index.orphans(),
+    };
+
+    let mut paths = orphans
+        .iter()
+        .map(|n| vault.relative_path(n.path()))
+        .collect::<Vec<_>>();
+    paths.sort();
+
+    for path in paths {
+        print_record(path.display(), null);
+    }
+}
+
+/// Version of the event stream's records, in its leading `meta` record.
+/// Fields may be added without changing it; it changes when a field is
+/// removed or its meaning changes.
+const EVENTS_VERSION: u32 = 1;
+
+#[derive(Serialize)]
+struct MetaEvent {
+    version: u32,
+}
+
+/// Starts a note's links, replacing whatever was written for it before.
+#[derive(Serialize)]
+struct NoteEvent<'a> {
+    path: &'a str,
+    links: usize,
+}
+
+#[derive(Serialize)]
+struct RemovedEvent<'a> {
+    path: &'a str,
+}
 
-                for found_match in matches {
-                    // dbg!(&found_match);
-                    let nodes = found_match
-                        .nodes_for_capture_index(1)
-                        .into_iter()
-                        .par_bridge()
-                        .collect::<Vec<tree_sitter::Node>>();
-
-                    // dbg!(nodes);
-                    for node in nodes {
-                        let alias = frontmatter_content[node.byte_range()].to_string();
-                        // dbg!(&alias);
-                        aliases.push(alias);
+#[derive(Clone, PartialEq, Serialize)]
+struct LinkEvent {
+    source: String,
+    /// Byte range of the link in the file, not counting a byte order mark.
+    start: usize,
+    end: usize,
+    /// Where the link starts, counting from 0 as editors speaking the
+    /// language server protocol do, unlike the 1-based `line` of `links`.
+    /// The column is in bytes.
+    line: usize,
+    column: usize,
+    destination: String,
+    status: LinkStatus,
+    /// The note or file the link leads to.
+    target: Option<String>,
+}
+
+#[derive(Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+enum LinkStatus {
+    Note,
+    Attachment,
+    External,
+    Broken,
+    /// The note exists but the `#heading` or `#^block` doesn't.
+    BrokenAnchor,
+}
+
+/// The link event stream: a `meta` record, then for each note a `note`
+/// record followed by one `link` record per link, with every path canonical
+/// so a reader needn't know where darkstone was run from. Under `--watch` it keeps
+/// what it last wrote, and writes the notes whose links changed, including
+/// links elsewhere that a new or renamed note fixed or broke, and a
+/// `removed` record for each note gone from the vault.
+struct Events {
+    notes: BTreeMap<String, Vec<LinkEvent>>,
+}
+
+impl Events {
+    fn new(vault: &Vault) -> Self {
+        Self {
+            notes: link_events(vault),
+        }
+    }
+
+    fn write_all(&self) -> std::io::Result<()> {
+        write_record(
+            "meta",
+            MetaEvent {
+                version: EVENTS_VERSION,
+            },
+        )?;
+        for (path, links) in &self.notes {
+            write_note_events(path, links)?;
+        }
+
+        Ok(())
+    }
+
+    fn update(&mut self, vault: &Vault) -> std::io::Result<()> {
+        let notes = link_events(vault);
+
+        for path in self.notes.keys().filter(|p| !notes.contains_key(*p)) {
+            write_record("removed", RemovedEvent { path })?;
+        }
+        for (path, links) in &notes {
+            if self.notes.get(path) != Some(links) {
+                write_note_events(path, links)?;
+            }
+        }
+        self.notes = notes;
+
+        Ok(())
+    }
+}
+
+fn write_note_events(path: &str, links: &[LinkEvent]) -> std::io::Result<()> {
+    write_record(
+        "note",
+        NoteEvent {
+            path,
+            links: links.len(),
+        },
+    )?;
+    for link in links {
+        write_record("link", link)?;
+    }
+
+    Ok(())
+}
+
+/// Every note's links in the order they appear, with where each leads, by
+/// the note's canonical path.
+fn link_events(vault: &Vault) -> BTreeMap<String, Vec<LinkEvent>> {
+    let index = vault.index();
+    let attachments = index
+        .attachments()
+        .into_iter()
+        .filter_map(|a| Some(((a.link.source, a.link.start), a.path?)))
+        .collect::<HashMap<_, _>>();
+    let anchors = index
+        .broken_anchors()
+        .into_iter()
+        .map(|l| (l.source, l.start))
+        .collect::<HashSet<_>>();
+
+    let mut notes = BTreeMap::new();
+    for note in vault.notes() {
+        let mut links = note.links();
+        links.sort_by_key(|l| l.start);
+
+        let events = links
+            .into_iter()
+            .map(|link| {
+                let key = (link.source.clone(), link.start);
+                // `[[#Heading]]` is a link to its own note.
+                let resolved = match link.destination.is_empty() {
+                    true => Some(note),
+                    false => index.resolve_link(&link),
+                };
+                let (status, target) = match (link.is_external(), resolved) {
+                    (true, _) => (LinkStatus::External, None),
+                    (false, Some(n)) if anchors.contains(&key) => {
+                        (LinkStatus::BrokenAnchor, Some(n.path()))
                     }
+                    (false, Some(n)) => (LinkStatus::Note, Some(n.path())),
+                    (false, None) => match attachments.get(&key) {
+                        Some(path) => (LinkStatus::Attachment, Some(path.as_path())),
+                        None => (LinkStatus::Broken, None),
+                    },
+                };
+
+                LinkEvent {
+                    source: canonical(&link.source),
+                    start: link.start.byte,
+                    end: link.end.byte,
+                    line: link.start.line,
+                    column: link.start.column,
+                    destination: link.raw_destination.clone(),
+                    status,
+                    target: target.map(canonical),
                 }
+            })
+            .collect();
+        notes.insert(canonical(note.path()), events);
+    }
+
+    notes
+}
 
-                aliases
+/// `path` made absolute with any symlinks followed, or just absolute if it no
+/// longer exists.
+fn canonical(path: &Path) -> String {
+    std::fs::canonicalize(path)
+        .or_else(|_| std::path::absolute(path))
+        .unwrap_or(path.to_path_buf())
+        .display()
+        .to_string()
+}
+
+fn print_links(vault: &Vault, format: LinksFormat, header: bool) -> csv::Result<()> {
+    match format {
+        LinksFormat::Text => {
+            for link in vault.links() {
+                println!(
+                    "{}: {} -> {}",
+                    vault.relative_path(&link.source).display(),
+                    link.text,
+                    link.destination
+                );
             }
-            None => vec![],
         }
+        LinksFormat::Csv => export::links_csv(vault, std::io::stdout(), header)?,
+        LinksFormat::Jsonl => {
+            for link in vault.links() {
+                let link = JsonLink {
+                    source: vault.relative_path(&link.source).display().to_string(),
+                    line: link.start.line + 1,
+                    kind: link.kind,
+                    destination: &link.destination,
+                    text: &link.text,
+                    raw: &link.raw,
+                    is_embed: link.is_embed,
+                    heading: link.heading.as_deref(),
+                    block: link.block.as_deref(),
+                };
+                write_record("link", link)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn print_urls(vault: &Vault) {
+    let links = vault.external_links();
+    let urls = links
+        .iter()
+        .map(|l| (l.destination.as_str(), vault.relative_path(&l.source)))
+        .into_group_map();
+
+    for (url, sources) in urls.into_iter().sorted() {
+        let sources = sources.iter().unique().map(|s| s.display()).join("\t");
+        println!("{}\t{}", url, sources);
+    }
+}
+
+fn print_attachments(vault: &Vault, missing: bool, unused: bool, null: bool) {
+    let index = vault.index();
+    if unused {
+        for path in index.unused_attachments() {
+            print_record(vault.relative_path(&path).display(), null);
+        }
+        return;
     }
 
-    fn parsed_frontmatter(&self) -> Option<Tree> {
-        match self.frontmatter() {
-            Some(frontmatter) => {
-                let mut parser = Parser::new();
-                parser
-                    .set_language(&tree_sitter_yaml::language())
-                    .expect("Error loading Markdown grammar");
+    let attachments = index
+        .attachments()
+        .into_iter()
+        .filter(|a| !missing || a.is_missing())
+        .sorted_by(|a, b| {
+            a.link
+                .source
+                .cmp(&b.link.source)
+                .then_with(|| a.link.destination.cmp(&b.link.destination))
+        });
+
+    for attachment in attachments {
+        let found = match attachment.path() {
+            Some(path) => vault.relative_path(path).display().to_string(),
+            None => format!("{BROKEN}(missing){BROKEN:#}"),
+        };
+        println!(
+            "{SOURCE}{}:{SOURCE:#} {} -> {}",
+            vault.relative_path(&attachment.link.source).display(),
+            attachment.link.destination,
+            found
+        );
+    }
+}
 
-                let tree: tree_sitter::Tree = parser.parse(frontmatter, None).unwrap();
+/// Prints tags with their `counts`, by tag or, if `by_count`, most common
+/// first.
+fn print_tags(
+    counts: &HashMap<String, usize>,
+    format: Format,
+    by_count: bool,
+) -> std::io::Result<()> {
+    let tags = counts
+        .iter()
+        .sorted_by(|(a, a_count), (b, b_count)| match by_count {
+            true => b_count.cmp(a_count).then_with(|| a.cmp(b)),
+            false => a.cmp(b),
+        })
+        .map(|(tag, count)| JsonTag { tag, count: *count });
 
-                Some(tree)
+    match format {
+        Format::Text => {
+            for tag in tags {
+                println!("#{} {COUNT}{}{COUNT:#}", tag.tag, tag.count);
+            }
+        }
+        Format::Json => println!("{}", serde_json::to_string(&tags.collect::<Vec<_>>())?),
+        Format::Jsonl => {
+            for tag in tags {
+                write_record("tag", tag)?;
             }
-            None => None,
         }
     }
 
-    fn frontmatter(&self) -> Option<String> {
-        let mut cursor = self.tree.walk();
-        cursor.goto_first_child();
-        let node = cursor.node();
+    Ok(())
+}
+
+fn print_tag_pairs(vault: &Vault, format: Format, min: usize) -> std::io::Result<()> {
+    let pairs = vault.tag_cooccurrence();
+    let pairs = pairs
+        .iter()
+        .take_while(|(_, count)| *count >= min)
+        .map(|((a, b), count)| JsonTagPair {
+            tags: [a, b],
+            count: *count,
+        });
 
-        match node.kind() {
-            "minus_metadata" => Some(self.content[node.start_byte()..node.end_byte()].to_string()),
-            _ => None,
+    match format {
+        Format::Text => {
+            for pair in pairs {
+                println!(
+                    "#{} #{} {COUNT}{}{COUNT:#}",
+                    pair.tags[0], pair.tags[1], pair.count
+                );
+            }
+        }
+        Format::Json => println!("{}", serde_json::to_string(&pairs.collect::<Vec<_>>())?),
+        Format::Jsonl => {
+            for pair in pairs {
+                write_record("tag_pair", pair)?;
+            }
         }
     }
 
-    fn links(&self) -> Vec<Link> {
-        let mut links = self.frontmatter_links();
-        links.append(&mut self.body_links(&self.tree, self.content.clone()));
+    Ok(())
+}
+
+fn rename(
+    vault: &Vault,
+    note: &str,
+    new_name: &str,
+    dry_run: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let rename = Rename::plan(vault, note, new_name)?;
 
-        links
+    for edit in &rename.edits {
+        println!(
+            "{}:{}: {} -> {}",
+            vault.relative_path(&edit.path).display(),
+            edit.line + 1,
+            edit.original,
+            edit.replacement
+        );
     }
+    println!(
+        "{} -> {}",
+        vault.relative_path(&rename.from).display(),
+        vault.relative_path(&rename.to).display()
+    );
 
-    fn frontmatter_links(&self) -> Vec<Link> {
-        let mut links = std::vec![];
-        let mut query_cursor = QueryCursor::new();
+    if !dry_run {
+        rename.apply()?;
+    }
 
-        match self.parsed_frontmatter() {
-            Some(tree) => {
-                let frontmatter = self.frontmatter().unwrap();
-                let matches = query_cursor.matches(
-                    &self.query_cache.frontmatter_links,
-                    tree.root_node(),
-                    frontmatter.as_bytes(),
-                );
+    Ok(())
+}
 
-                for found_match in matches {
-                    let node = found_match
-                        .nodes_for_capture_index(0)
-                        .collect::<Vec<tree_sitter::Node>>()
-                        .pop()
-                        .unwrap();
-
-                    let scalar = self.content[node.byte_range()].to_string();
-                    let mut parser = MarkdownParser::default();
-                    let tree = parser.parse(scalar.as_bytes(), None).unwrap();
-                    let mut parsed_links = self.body_links(&tree, scalar.clone());
-                    links.append(&mut parsed_links);
-                }
+fn print_outline(vault: &Vault, note: &str) -> Result<(), String> {
+    let note = match vault.resolve(note) {
+        Some(note) => note,
+        None => return Err(format!("no note named `{}`", note)),
+    };
 
-                links
+    for heading in note.headings() {
+        let indent = "  ".repeat(usize::from(heading.level.saturating_sub(1)));
+        println!("{}{}", indent, heading.text);
+    }
+
+    Ok(())
+}
+
+fn print_tasks(vault: &Vault) {
+    let (mut open, mut done) = (0, 0);
+
+    for note in vault.notes() {
+        for task in note.tasks() {
+            if task.done {
+                done += 1;
+            }
+            if task.is_open() {
+                open += 1;
+                println!(
+                    "{SOURCE}{}:{}:{SOURCE:#} [{}] {}",
+                    vault.relative_path(note.path()).display(),
+                    task.line + 1,
+                    task.status,
+                    task.text
+                );
             }
-            None => std::vec![],
         }
     }
 
-    fn body_links(&self, tree: &MarkdownTree, content: String) -> Vec<Link> {
-        let mut links = std::vec![];
+    println!(
+        "{COUNT}{}{COUNT:#} open, {COUNT}{}{COUNT:#} done",
+        open, done
+    );
+}
+
+fn print_query(
+    vault: &Vault,
+    query: &str,
+    capture: Option<&str>,
+    grammar: QueryGrammar,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let grammar = Grammar::from(grammar);
+    let query = grammar.query(query)?;
+    if let Some(capture) = capture {
+        if query.capture_index_for_name(capture).is_none() {
+            return Err(format!("query has no @{} capture", capture).into());
+        }
+    }
+
+    for note in vault.notes() {
+        let captures = note
+            .captures(&query, grammar)
+            .into_iter()
+            .filter(|c| capture.is_none_or(|name| c.name == name));
+
+        for capture in captures {
+            println!(
+                "{SOURCE}{}:{}:{}:{SOURCE:#} {}",
+                vault.relative_path(note.path()).display(),
+                capture.start.line + 1,
+                capture.start.column + 1,
+                capture.text
+            );
+        }
+    }
 
-        for inline_tree in tree.inline_trees() {
-            let mut query_cursor = QueryCursor::new();
-            let matches = query_cursor.matches(
-                &self.query_cache.body_query,
-                inline_tree.root_node(),
-                content.as_bytes(),
+    Ok(())
+}
+
+fn print_callouts(vault: &Vault, kind: Option<&str>) {
+    for note in vault.notes() {
+        let callouts = note
+            .callouts()
+            .into_iter()
+            .filter(|c| kind.is_none_or(|kind| c.kind.eq_ignore_ascii_case(kind)));
+
+        for callout in callouts {
+            let title = match callout.title {
+                Some(title) => format!(" {}", title),
+                None => String::new(),
+            };
+            println!(
+                "{SOURCE}{}:{}:{SOURCE:#} [!{}]{}",
+                vault.relative_path(note.path()).display(),
+                callout.line + 1,
+                callout.kind,
+                title
+            );
+        }
+    }
+}
+
+fn print_wordcount(vault: &Vault, sort: Order, wpm: usize) {
+    let counts = vault
+        .notes()
+        .par_iter()
+        .map(|n| (n.word_count(), vault.relative_path(n.path())))
+        .collect::<Vec<(usize, &Path)>>();
+    let counts = counts.iter().sorted_by(|a, b| match sort {
+        Order::Asc => a.cmp(b),
+        Order::Desc => b.0.cmp(&a.0).then_with(|| a.1.cmp(b.1)),
+    });
+
+    let mut total = 0;
+    for (count, path) in counts {
+        total += count;
+        println!("{COUNT}{}{COUNT:#} {}", count, path.display());
+    }
+
+    let minutes = (total as f64 / wpm.max(1) as f64).ceil();
+    println!(
+        "{COUNT}{}{COUNT:#} words in total, about {} min to read",
+        total, minutes
+    );
+}
+
+fn parse_predicate(predicate: &str) -> Result<(String, String), String> {
+    match predicate.split_once('=') {
+        Some((key, value)) => Ok((key.trim().to_string(), value.trim().to_string())),
+        None => Err(format!("expected key=value, got `{}`", predicate)),
+    }
+}
+
+fn print_filter(vault: &Vault, predicates: &[(String, String)], tags: &[String], null: bool) {
+    let paths = vault
+        .notes()
+        .iter()
+        .filter(|n| {
+            predicates
+                .iter()
+                .all(|(key, value)| n.field(key).is_some_and(|actual| actual.matches(value)))
+        })
+        .filter(|n| tags.iter().all(|tag| n.has_tag(tag)))
+        .map(|n| vault.relative_path(n.path()))
+        .sorted();
+
+    for path in paths {
+        print_record(path.display(), null);
+    }
+}
+
+fn print_graph(vault: &Vault, format: GraphFormat, include_broken: bool) -> serde_json::Result<()> {
+    match format {
+        GraphFormat::Dot => print!("{}", export::dot(vault, include_broken)),
+        GraphFormat::D3Json => println!("{}", export::d3_json(vault, include_broken)?),
+        GraphFormat::Graphml => print!("{}", export::graphml(vault, include_broken)),
+    }
+
+    Ok(())
+}
+
+fn print_stats(vault: &Vault, format: Format) -> std::io::Result<()> {
+    let stats = vault.stats();
+
+    match format {
+        Format::Text => {
+            println!("{:<16}{COUNT}{}{COUNT:#}", "Notes", stats.notes);
+            println!(
+                "{:<16}{COUNT}{}{COUNT:#}",
+                "Outbound links", stats.outbound_links
+            );
+            println!(
+                "{:<16}{COUNT}{}{COUNT:#}",
+                "Resolved links", stats.resolved_links
+            );
+            println!(
+                "{:<16}{COUNT}{}{COUNT:#}",
+                "External links", stats.external_links
             );
+            println!(
+                "{:<16}{COUNT}{}{COUNT:#}",
+                "Broken links", stats.broken_links
+            );
+            println!(
+                "{:<16}{COUNT}{}{COUNT:#}",
+                "Unique targets", stats.unique_targets
+            );
+            println!("{:<16}{COUNT}{}{COUNT:#}", "Orphans", stats.orphans);
+            println!(
+                "{:<16}{COUNT}{:.2}{COUNT:#}",
+                "Links per note", stats.links_per_note
+            );
+        }
+        Format::Json => println!("{}", serde_json::to_string(&stats)?),
+        Format::Jsonl => write_record("stats", stats)?,
+    }
+
+    Ok(())
+}
+
+fn print_folder_stats(vault: &Vault, format: Format) -> std::io::Result<()> {
+    let folders = vault.folder_stats();
 
-            for found_match in matches {
-                // index 0 is destination
-                // index 1 is text
-                //
-                // Frontmatter "[[Foo]]" will come in as a wiki_link with just a destination
-                // Frontmatter "[[Foo|bar]]" will come in as a wiki_link with text and destination
-                // Body [[Bar]] will come in as a shortcut_link with just text
-                // then body [[dest|name]] will come in as wiki_link with text and destination
-                let dest_node = found_match
-                    .nodes_for_capture_index(0)
-                    .collect::<Vec<tree_sitter::Node>>()
-                    .pop();
-
-                let text_node = found_match
-                    .nodes_for_capture_index(1)
-                    .collect::<Vec<tree_sitter::Node>>()
-                    .pop();
-
-                // If there is no destination, text is used for both
-                // If there is no text, destination is used for both
-                let dest_node = dest_node.unwrap_or_else(|| text_node.unwrap());
-                let text_node = text_node.unwrap_or_else(|| dest_node);
-
-                let destination = content[dest_node.byte_range()].to_string();
-                let text = content[text_node.byte_range()].to_string();
-
-                links.push(Link { destination, text });
+    match format {
+        Format::Text => {
+            let width = folders
+                .iter()
+                .map(|f| f.folder.chars().count())
+                .max()
+                .unwrap_or(0)
+                .max("Folder".len())
+                + 2;
+            println!(
+                "{:<width$}{:>8}{:>8}{:>8}",
+                "Folder", "Notes", "Links", "Orphans"
+            );
+            for folder in &folders {
+                let name = match folder.folder.is_empty() {
+                    true => "/",
+                    false => folder.folder.as_str(),
+                };
+                println!(
+                    "{:<width$}{COUNT}{:>8}{:>8}{:>8}{COUNT:#}",
+                    name, folder.notes, folder.links, folder.orphans
+                );
+            }
+        }
+        Format::Json => println!("{}", serde_json::to_string(&folders)?),
+        Format::Jsonl => {
+            for folder in folders {
+                write_record("folder", folder)?;
             }
         }
+    }
+
+    Ok(())
+}
 
-        links
+fn print_rank(vault: &Vault, damping: f64, iterations: usize) {
+    for (note, score) in vault.pagerank(damping, iterations) {
+        println!(
+            "{:.6} {}",
+            score,
+            vault.relative_path(note.path()).display()
+        );
     }
 }
 
-#[derive(Debug)]
-struct Link {
-    destination: String,
-    text: String,
+fn print_components(vault: &Vault) {
+    for component in vault.components() {
+        println!(
+            "{COUNT}{}{COUNT:#} {}",
+            component.len(),
+            vault.relative_path(component[0].path()).display()
+        );
+    }
 }
 
-/// Simple program to greet a person
-#[derive(ClapParser, Debug)]
-#[command()]
-struct Args {
-    #[arg(short, long)]
-    vault_path: PathBuf,
+fn print_neighbors(vault: &Vault, note: &str, depth: usize, directed: Option<Directed>) {
+    let direction = match directed {
+        Some(Directed::Out) => Direction::Out,
+        Some(Directed::In) => Direction::In,
+        None => Direction::Both,
+    };
+
+    let neighbors = vault.neighbors(note, depth, direction);
+    for (distance, notes) in &neighbors.iter().chunk_by(|(_, distance)| *distance) {
+        println!("{COUNT}{}{COUNT:#}", distance);
+        for (note, _) in notes {
+            println!("    {}", vault.relative_path(note.path()).display());
+        }
+    }
 }
 
-fn main() {
-    let args = Args::parse();
-    let vault = Vault::new(args.vault_path);
+fn print_cycles(vault: &Vault, max: usize) {
+    for cycle in vault.cycles(max) {
+        let path = |n: &Note| vault.relative_path(n.path()).display().to_string();
+        println!(
+            "{} -> {}",
+            cycle.iter().map(|n| path(n)).join(" -> "),
+            path(cycle[0])
+        );
+    }
+}
 
-    // let notes = vault.notes();
-    // let note = notes
-    //     .iter()
-    //     .find(|n| n.name() == "1716397370-SYPL")
-    //     .unwrap();
-    // dbg!(note.targets());
+fn print_duplicates(vault: &Vault) {
+    for (name, paths) in vault.duplicate_names() {
+        println!("{}", name);
+        for path in paths {
+            println!("    {}", vault.relative_path(&path).display());
+        }
+    }
+}
+
+fn print_templates(vault: &Vault, null: bool) {
+    for note in vault.templates() {
+        print_record(vault.relative_path(note.path()).display(), null);
+    }
+}
+
+fn print_aliases(vault: &Vault) {
+    let aliases = vault.alias_map();
+
+    for (alias, note) in aliases.iter().sorted_by(|(a, _), (b, _)| natural_cmp(a, b)) {
+        println!("{} -> {}", alias, note);
+    }
+    for (alias, paths) in vault.alias_collisions() {
+        let paths = paths
+            .iter()
+            .map(|p| vault.relative_path(p).display())
+            .join(", ");
+        warn!("alias {} is claimed by {}", alias, paths);
+    }
+}
+
+fn print_popular(vault: &Vault, top: usize) {
+    let counts = vault.inbound_counts();
+    let popular = counts
+        .iter()
+        .map(|(path, count)| (vault.relative_path(path), count))
+        .sorted_by(|(a_path, a_count), (b_path, b_count)| {
+            b_count.cmp(a_count).then_with(|| a_path.cmp(b_path))
+        })
+        .take(top);
+
+    for (path, count) in popular {
+        println!("{COUNT}{}{COUNT:#} {}", count, path.display());
+    }
+}
+
+/// Compares names the way a reader would: case and accents only break ties,
+/// and runs of digits compare by their value. Names equal by that measure
+/// fall back to byte order, so the order is still total.
+fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let (a_key, b_key) = (collation_key(a), collation_key(b));
+    let (mut a_key, mut b_key) = (a_key.as_slice(), b_key.as_slice());
 
-    let mut targets = vault.targets();
-    targets.sort();
-    for target in targets {
-        println!("{}", target);
+    loop {
+        let ordering = match (a_key.first(), b_key.first()) {
+            (None, None) => return a.cmp(b),
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) if x.is_ascii_digit() && y.is_ascii_digit() => {
+                let a_len = a_key.iter().take_while(|c| c.is_ascii_digit()).count();
+                let b_len = b_key.iter().take_while(|c| c.is_ascii_digit()).count();
+                let (a_digits, b_digits) = (&a_key[..a_len], &b_key[..b_len]);
+                a_key = &a_key[a_len..];
+                b_key = &b_key[b_len..];
+
+                // Leading zeros don't change a number's value.
+                let a_digits = trim_zeros(a_digits);
+                let b_digits = trim_zeros(b_digits);
+                a_digits
+                    .len()
+                    .cmp(&b_digits.len())
+                    .then(a_digits.cmp(b_digits))
+            }
+            (Some(x), Some(y)) => {
+                a_key = &a_key[1..];
+                b_key = &b_key[1..];
+                x.cmp(y)
+            }
+        };
+
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+}
+
+/// `name` lowercased, with accents and other combining marks removed.
+fn collation_key(name: &str) -> Vec<char> {
+    name.nfd()
+        .filter(|c| !is_combining_mark(*c))
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+fn trim_zeros(digits: &[char]) -> &[char] {
+    let zeros = digits.iter().take_while(|c| **c == '0').count();
+
+    &digits[zeros..]
+}
+
+/// Prints one entry of a list, ended by a newline or, with `null`, a NUL
+/// byte. anstream would strip the NUL along with any color, so it is
+/// printed without it; list entries are never colored anyway.
+fn print_record(record: impl std::fmt::Display, null: bool) {
+    match null {
+        true => std::print!("{}\0", record),
+        false => println!("{}", record),
     }
+}
 
-    // for link in vault.links() {
-    //     println!("{} -> {}", link.text, link.destination);
-    // }
+/// Writes `item` to stdout as one line of JSON Lines. The line goes out in a
+/// single write, so a reader that stops early never sees half a record.
+fn write_record<T: Serialize>(kind: &'static str, item: T) -> std::io::Result<()> {
+    let mut line = serde_json::to_vec(&Record { kind, item })?;
+    line.push(b'\n');
 
-    // println!("Notes: {}", vault.notes().len());
-    // println!("Links: {}", vault.links().len());
+    std::io::stdout().lock().write_all(&line)
 }
@@ -0,0 +1,1515 @@
+use crate::daily::{self, DAILY_FORMAT};
+use crate::frontmatter::{self, FrontmatterValue};
+use crate::heading::heading_key;
+use crate::link::{nfc, target_name};
+use crate::{
+    Callout, Capture, DarkstoneError, Grammar, Heading, Link, LinkKind, Position, QueryCache,
+    Target, Task,
+};
+use chrono::NaiveDate;
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+use tracing::{debug, trace};
+use tree_sitter::{Node, Parser, Query, QueryCursor, Tree};
+use tree_sitter_md::{MarkdownParser, MarkdownTree};
+
+#[derive(Clone, Debug)]
+pub struct Note {
+    path: PathBuf,
+    /// Read and parsed on first use for summarized notes.
+    source: OnceLock<Source>,
+    /// What was extracted when the note was last parsed, for notes that came
+    /// from the cache or were summarized to save memory.
+    summary: Option<NoteSummary>,
+    query_cache: Arc<QueryCache>,
+    /// Whether links inside comments count. See `with_comments`.
+    include_comments: bool,
+}
+
+/// What is extracted from a note: its name, aliases, tags and links, without
+/// the note's content or syntax tree.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct NoteSummary {
+    pub path: PathBuf,
+    pub name: String,
+    pub aliases: Vec<String>,
+    pub frontmatter_tags: Vec<String>,
+    pub inline_tags: Vec<String>,
+    pub links: Vec<Link>,
+    pub headings: Vec<Heading>,
+    pub block_ids: Vec<String>,
+    pub is_lossy: bool,
+    pub is_template: bool,
+}
+
+impl NoteSummary {
+    /// Frontmatter tags followed by inline body tags, as `Note::tags`.
+    pub fn tags(&self) -> Vec<String> {
+        let mut tags = self.frontmatter_tags.clone();
+        tags.extend(self.inline_tags.iter().cloned());
+
+        tags
+    }
+}
+
+thread_local! {
+    // Setting up a parser loads its grammar, which costs more than parsing a
+    // typical note, so each rayon thread keeps one of each for every note it
+    // parses.
+    static MARKDOWN_PARSER: RefCell<MarkdownParser> = RefCell::new(MarkdownParser::default());
+    static YAML_PARSER: RefCell<Option<Parser>> = RefCell::new(yaml_parser());
+}
+
+/// `QueryCache::new` has already checked the grammar loads, so this is only
+/// `None` in theory.
+fn yaml_parser() -> Option<Parser> {
+    let mut parser = Parser::new();
+    parser.set_language(&tree_sitter_yaml::language()).ok()?;
+
+    Some(parser)
+}
+
+/// The UTF-8 byte order mark.
+pub(crate) const BOM: char = '\u{feff}';
+
+#[derive(Clone, Debug)]
+struct Source {
+    content: String,
+    is_lossy: bool,
+    tree: MarkdownTree,
+    /// Blank lines cut from the start of the file to bring frontmatter to
+    /// the top, as bytes and as lines. Positions add these back.
+    skipped: (usize, usize),
+    /// YAML frontmatter, parsed the first time anything asks for it.
+    frontmatter: OnceLock<Option<Frontmatter>>,
+    /// Where each line of `content` starts, found the first time a position
+    /// is asked for so that placing every link is one search each.
+    line_starts: OnceLock<Vec<usize>>,
+}
+
+/// YAML frontmatter with its syntax tree. Nodes of the tree index `text`,
+/// which starts `start` bytes into the parsed content.
+#[derive(Clone, Debug)]
+struct Frontmatter {
+    text: String,
+    start: usize,
+    tree: Tree,
+}
+
+impl Source {
+    fn read(path: &Path, lossy: bool) -> Result<Self, DarkstoneError> {
+        let bytes = match std::fs::read(path) {
+            Ok(bytes) => bytes,
+            Err(err) => return Err(DarkstoneError::Io(path.to_path_buf(), err)),
+        };
+        // Everything after this works on the decoded string, so byte ranges
+        // always index the replacement characters rather than the raw bytes.
+        let (content, is_lossy) = match String::from_utf8(bytes) {
+            Ok(content) => (content, false),
+            Err(err) if lossy => (String::from_utf8_lossy(err.as_bytes()).to_string(), true),
+            Err(err) => return Err(DarkstoneError::Utf8(path.to_path_buf(), err)),
+        };
+
+        Self::parse(content, is_lossy).ok_or_else(|| DarkstoneError::Parse(path.to_path_buf()))
+    }
+
+    fn parse(content: String, is_lossy: bool) -> Option<Self> {
+        // Editors on Windows like to start files with a byte order mark,
+        // which would hide JSON frontmatter and skew first-line columns.
+        let content = match content.strip_prefix(BOM) {
+            Some(content) => content.to_string(),
+            None => content,
+        };
+        // The grammar only takes `---` on the very first line as the start
+        // of frontmatter, so blank lines in front of it would hide it.
+        let blank = content
+            .split_inclusive('\n')
+            .take_while(|line| line.ends_with('\n') && line.trim().is_empty())
+            .collect::<Vec<&str>>();
+        let skipped = blank.iter().map(|line| line.len()).sum::<usize>();
+        let (content, skipped) = match content[skipped..].starts_with("---") {
+            true => (content[skipped..].to_string(), (skipped, blank.len())),
+            false => (content, (0, 0)),
+        };
+
+        let tree = MARKDOWN_PARSER.with_borrow_mut(|p| p.parse(content.as_bytes(), None))?;
+
+        Some(Self {
+            content,
+            is_lossy,
+            tree,
+            skipped,
+            frontmatter: OnceLock::new(),
+            line_starts: OnceLock::new(),
+        })
+    }
+}
+
+impl Note {
+    pub fn new(path: PathBuf, query_cache: Arc<QueryCache>) -> Result<Self, DarkstoneError> {
+        Self::load(path, query_cache, false)
+    }
+
+    /// Like `new`, but invalid UTF-8 is replaced with U+FFFD instead of
+    /// failing. Check `is_lossy` to find out whether anything was replaced.
+    pub fn new_lossy(path: PathBuf, query_cache: Arc<QueryCache>) -> Result<Self, DarkstoneError> {
+        Self::load(path, query_cache, true)
+    }
+
+    /// A note with `content` rather than whatever is at `path`, such as an
+    /// editor buffer or text piped in. `path` is only used for the note's
+    /// name and the links' `source`, so it needn't exist, but the note can't
+    /// be summarized if it doesn't.
+    pub fn from_content(
+        path: PathBuf,
+        content: String,
+        query_cache: Arc<QueryCache>,
+    ) -> Result<Self, DarkstoneError> {
+        let source = match Source::parse(content, false) {
+            Some(source) => source,
+            None => return Err(DarkstoneError::Parse(path)),
+        };
+
+        Ok(Self::with_source(path, source, query_cache))
+    }
+
+    /// A note called `name` holding `content`, parsed without touching the
+    /// file system. Its path is `name.md`, relative to nowhere in
+    /// particular, so a `name` like `folder/Note` puts it in a folder.
+    pub fn from_str(
+        name: &str,
+        content: &str,
+        query_cache: Arc<QueryCache>,
+    ) -> Result<Self, DarkstoneError> {
+        let path = PathBuf::from(format!("{}.md", name));
+
+        Self::from_content(path, content.to_string(), query_cache)
+    }
+
+    pub(crate) fn load(
+        path: PathBuf,
+        query_cache: Arc<QueryCache>,
+        lossy: bool,
+    ) -> Result<Self, DarkstoneError> {
+        let source = Source::read(&path, lossy)?;
+
+        Ok(Self::with_source(path, source, query_cache))
+    }
+
+    fn with_source(path: PathBuf, source: Source, query_cache: Arc<QueryCache>) -> Self {
+        debug!(
+            path = %path.display(),
+            bytes = source.content.len(),
+            lossy = source.is_lossy,
+            "parsed note"
+        );
+
+        Self {
+            path,
+            source: OnceLock::from(source),
+            summary: None,
+            query_cache,
+            include_comments: false,
+        }
+    }
+
+    /// A note whose links, aliases and tags come from `summary`. The file is
+    /// only read if something else about it is asked for.
+    pub(crate) fn from_summary(
+        path: PathBuf,
+        mut summary: NoteSummary,
+        query_cache: Arc<QueryCache>,
+    ) -> Self {
+        // The vault may have been opened by another path last time.
+        summary.path = path.clone();
+        for link in &mut summary.links {
+            link.source = path.clone();
+        }
+
+        Self {
+            path,
+            source: OnceLock::new(),
+            summary: Some(summary),
+            query_cache,
+            include_comments: false,
+        }
+    }
+
+    /// Keeps only the note's summary, dropping its content and syntax tree.
+    /// Anything not in the summary is still available, but the file is read
+    /// and parsed again the first time it is asked for.
+    pub fn summarized(self) -> Self {
+        if self.summary.is_some() {
+            return self;
+        }
+        let summary = self.summary();
+
+        Self {
+            path: self.path,
+            source: OnceLock::new(),
+            summary: Some(summary),
+            query_cache: self.query_cache,
+            include_comments: self.include_comments,
+        }
+    }
+
+    /// Whether to take links from inside `<!-- HTML comments -->` and
+    /// `%% Obsidian comments %%`, which `links` leaves out by default. Has no
+    /// effect once the note is summarized.
+    pub fn with_comments(mut self, include: bool) -> Self {
+        self.include_comments = include;
+        self
+    }
+
+    pub(crate) fn query_cache(&self) -> &Arc<QueryCache> {
+        &self.query_cache
+    }
+
+    pub fn summary(&self) -> NoteSummary {
+        match &self.summary {
+            Some(summary) => summary.clone(),
+            None => NoteSummary {
+                path: self.path.clone(),
+                name: self.name(),
+                aliases: self.aliases(),
+                frontmatter_tags: self.frontmatter_tags(),
+                inline_tags: self.inline_tags(),
+                links: self.links(),
+                headings: self.headings(),
+                block_ids: self.block_ids(),
+                is_lossy: self.is_lossy(),
+                is_template: self.is_template(),
+            },
+        }
+    }
+
+    /// Whether the file contained invalid UTF-8 that was replaced on load.
+    pub fn is_lossy(&self) -> bool {
+        match &self.summary {
+            Some(summary) => summary.is_lossy,
+            None => self.source().is_lossy,
+        }
+    }
+
+    fn source(&self) -> &Source {
+        // Only summarized notes get here without a source, and they were
+        // valid when summarized. If the file has since become unreadable the
+        // note is treated as empty until the next scan notices.
+        self.source.get_or_init(|| {
+            Source::read(&self.path, true)
+                .ok()
+                .or_else(|| Source::parse(String::new(), false))
+                .expect("Error loading Markdown grammar")
+        })
+    }
+
+    fn content(&self) -> &str {
+        &self.source().content
+    }
+
+    fn tree(&self) -> &MarkdownTree {
+        &self.source().tree
+    }
+
+    /// The note's own name, `name|alias` for each alias, then every link
+    /// destination reduced to the note name Obsidian would resolve it by:
+    ///
+    /// - folders are dropped, so `folder/Note` becomes `Note`
+    /// - a trailing `.md`, `.markdown` or `.mdx` is dropped, so `Note.md` becomes `Note`
+    /// - other extensions are kept, since `img.png` names an attachment
+    /// - markdown links have `%20` style escapes decoded, so
+    ///   `[text](My%20Note.md#Heading)` gives `My Note`
+    /// - URLs are left exactly as written
+    ///
+    /// `[[Note]]`, `[[Note.md]]` and `[[folder/Note.md]]` all give `Note`.
+    pub fn targets(&self) -> Vec<Target> {
+        self.collect_targets(true)
+    }
+
+    /// Like `targets`, but with link destinations exactly as written.
+    pub fn raw_targets(&self) -> Vec<Target> {
+        self.collect_targets(false)
+    }
+
+    fn collect_targets(&self, normalize: bool) -> Vec<Target> {
+        let mut targets = vec![Target::Note(self.name())];
+        targets.append(&mut self.alias_targets());
+
+        let mut destinations = self
+            .links()
+            .iter()
+            .filter(|l| !l.destination.is_empty())
+            .map(|l| match normalize && !l.is_external() {
+                true => Target::Note(target_name(&l.decoded_destination()).to_string()),
+                false => Target::Note(l.destination.clone()),
+            })
+            .collect();
+        targets.append(&mut destinations);
+
+        // So one name is written one way, whichever way its file or the
+        // link to it was typed.
+        match normalize {
+            true => targets
+                .iter()
+                .map(|t| match t {
+                    Target::Note(note) => Target::Note(nfc(note)),
+                    Target::Alias { note, alias } => Target::Alias {
+                        note: nfc(note),
+                        alias: nfc(alias),
+                    },
+                })
+                .collect(),
+            false => targets,
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The file name without its extension. Any invalid UTF-8 in the name
+    /// is replaced rather than rejected.
+    pub fn name(&self) -> String {
+        match self.path.file_stem() {
+            Some(stem) => stem.to_string_lossy().to_string(),
+            None => String::new(),
+        }
+    }
+
+    /// The date the note is for, if it is a daily note named like
+    /// `2024-05-31`.
+    pub fn daily_date(&self) -> Option<NaiveDate> {
+        self.daily_date_with_format(DAILY_FORMAT)
+    }
+
+    /// Like `daily_date`, for daily notes named in another `chrono` format,
+    /// such as `%d.%m.%Y`.
+    pub fn daily_date_with_format(&self, format: &str) -> Option<NaiveDate> {
+        daily::daily_date(&self.name(), format)
+    }
+
+    /// Whether the note holds `{{date}}` style placeholders or Templater
+    /// `<% ... %>` tags, in its frontmatter or text, which make it a template
+    /// for other notes rather than a note of its own. Code doesn't count.
+    pub fn is_template(&self) -> bool {
+        if let Some(summary) = &self.summary {
+            return summary.is_template;
+        }
+
+        let mut texts = self.inline_texts();
+        texts.extend(self.parsed_frontmatter().map(|f| f.text.clone()));
+
+        texts
+            .iter()
+            .flat_map(|text| text.lines())
+            .any(|line| has_placeholder(line, "{{", "}}") || has_placeholder(line, "<%", "%>"))
+    }
+
+    fn alias_targets(&self) -> Vec<Target> {
+        let name = self.name();
+
+        self.aliases()
+            .into_iter()
+            .map(|alias| Target::Alias {
+                note: name.clone(),
+                alias,
+            })
+            .collect()
+    }
+
+    pub fn aliases(&self) -> Vec<String> {
+        if let Some(summary) = &self.summary {
+            return summary.aliases.clone();
+        }
+
+        if let Some(frontmatter) = self.structured_frontmatter() {
+            return string_list(&frontmatter, "aliases");
+        }
+
+        match self.parsed_frontmatter() {
+            Some(frontmatter) => {
+                let mut aliases = vec![];
+                let mut query_cursor = QueryCursor::new();
+                let matches = query_cursor.matches(
+                    &self.query_cache.alias_query,
+                    frontmatter.tree.root_node(),
+                    frontmatter.text.as_bytes(),
+                );
+
+                let aliases_index = self
+                    .query_cache
+                    .alias_query
+                    .capture_index_for_name("aliases")
+                    .unwrap();
+
+                for found_match in matches {
+                    // Kept sequential so aliases come out in document order.
+                    let nodes = found_match
+                        .nodes_for_capture_index(aliases_index)
+                        .collect::<Vec<tree_sitter::Node>>();
+
+                    for node in nodes {
+                        // A single scalar, or a block or flow sequence of
+                        // them, in any quoting style.
+                        let scalars = nodes_of_kind(
+                            node,
+                            &["plain_scalar", "single_quote_scalar", "double_quote_scalar"],
+                        );
+
+                        for scalar in scalars {
+                            let alias = frontmatter.text[scalar.byte_range()]
+                                .trim_matches(|c| c == '"' || c == '\'')
+                                .to_string();
+                            aliases.push(alias);
+                        }
+                    }
+                }
+
+                trace!(path = %self.path.display(), ?aliases, "aliases from frontmatter");
+                aliases
+            }
+            None => vec![],
+        }
+    }
+
+    /// Tags listed under the frontmatter `tags:` key, as a block sequence, a
+    /// flow sequence, or a single (possibly comma separated) scalar.
+    pub fn frontmatter_tags(&self) -> Vec<String> {
+        if let Some(summary) = &self.summary {
+            return summary.frontmatter_tags.clone();
+        }
+
+        if let Some(frontmatter) = self.structured_frontmatter() {
+            let mut tags = vec![];
+            for scalar in string_list(&frontmatter, "tags") {
+                push_tags(&mut tags, &scalar);
+            }
+
+            return tags;
+        }
+
+        match self.parsed_frontmatter() {
+            Some(frontmatter) => {
+                let mut tags = vec![];
+                let mut query_cursor = QueryCursor::new();
+                let matches = query_cursor.matches(
+                    &self.query_cache.tags_query,
+                    frontmatter.tree.root_node(),
+                    frontmatter.text.as_bytes(),
+                );
+
+                let value_index = self
+                    .query_cache
+                    .tags_query
+                    .capture_index_for_name("value")
+                    .unwrap();
+
+                for found_match in matches {
+                    let values = found_match.nodes_for_capture_index(value_index);
+
+                    for value in values {
+                        let scalars = nodes_of_kind(
+                            value,
+                            &["plain_scalar", "single_quote_scalar", "double_quote_scalar"],
+                        );
+
+                        for scalar in scalars {
+                            let scalar = frontmatter.text[scalar.byte_range()]
+                                .trim_matches(|c| c == '"' || c == '\'');
+                            push_tags(&mut tags, scalar);
+                        }
+                    }
+                }
+
+                tags
+            }
+            None => vec![],
+        }
+    }
+
+    /// The value of the top-level frontmatter `key`, or `None` if the note
+    /// has no frontmatter or doesn't set it.
+    pub fn frontmatter_value(&self, key: &str) -> Option<FrontmatterValue> {
+        if let Some(frontmatter) = self.structured_frontmatter() {
+            return frontmatter.get(key).map(FrontmatterValue::from);
+        }
+
+        let frontmatter = self.parsed_frontmatter()?;
+
+        frontmatter::yaml_value(frontmatter.tree.root_node(), &frontmatter.text, key)
+    }
+
+    /// Whether the note is tagged `tag`, or a tag nested under it, so `area`
+    /// matches `#area/subarea`. Case and a leading `#` are ignored.
+    pub fn has_tag(&self, tag: &str) -> bool {
+        let tag = tag.trim_start_matches('#').to_lowercase();
+
+        self.tags().iter().any(|t| {
+            let t = t.to_lowercase();
+            t == tag
+                || t.strip_prefix(&tag)
+                    .is_some_and(|rest| rest.starts_with('/'))
+        })
+    }
+
+    /// Frontmatter tags followed by inline body tags.
+    pub fn tags(&self) -> Vec<String> {
+        let mut tags = self.frontmatter_tags();
+        tags.append(&mut self.inline_tags());
+
+        tags
+    }
+
+    fn parsed_frontmatter(&self) -> Option<&Frontmatter> {
+        self.source()
+            .frontmatter
+            .get_or_init(|| {
+                let node = self.frontmatter_node()?;
+                let text = self.content()[node.byte_range()].to_string();
+                let tree = YAML_PARSER.with_borrow_mut(|p| p.as_mut()?.parse(&text, None))?;
+
+                Some(Frontmatter {
+                    text,
+                    start: node.start_byte(),
+                    tree,
+                })
+            })
+            .as_ref()
+    }
+
+    /// Frontmatter that isn't YAML: TOML between `+++` lines, or a JSON
+    /// object at the very start of the file.
+    fn structured_frontmatter(&self) -> Option<serde_json::Value> {
+        let mut cursor = self.tree().walk();
+        cursor.goto_first_child();
+        let node = cursor.node();
+
+        if node.kind() == "plus_metadata" {
+            let frontmatter = self.content()[node.byte_range()].trim();
+            let frontmatter = frontmatter
+                .strip_prefix("+++")
+                .and_then(|f| f.strip_suffix("+++"))
+                .unwrap_or(frontmatter);
+
+            return toml::from_str(frontmatter).ok();
+        }
+
+        if self.content().starts_with('{') {
+            let mut values = serde_json::Deserializer::from_str(self.content()).into_iter();
+            return match values.next() {
+                Some(Ok(value @ serde_json::Value::Object(_))) => Some(value),
+                _ => None,
+            };
+        }
+
+        None
+    }
+
+    fn frontmatter_node(&self) -> Option<Node<'_>> {
+        let mut cursor = self.tree().walk();
+        cursor.goto_first_child();
+        let node = cursor.node();
+
+        match node.kind() {
+            "minus_metadata" => Some(node),
+            _ => None,
+        }
+    }
+
+    pub fn links(&self) -> Vec<Link> {
+        if let Some(summary) = &self.summary {
+            return summary.links.clone();
+        }
+
+        let mut links = self.frontmatter_links();
+        links.append(&mut self.body_links(self.tree(), self.content().to_string(), 0));
+
+        // The grammar already keeps links out of HTML comments, but knows
+        // nothing of Obsidian's.
+        match self.include_comments {
+            true => links.append(&mut self.html_comment_links()),
+            false => {
+                let comments = self.obsidian_comments();
+                let skipped = self.source().skipped.0;
+                links.retain(|l| {
+                    !comments
+                        .iter()
+                        .any(|c| c.contains(&(l.start.byte - skipped)))
+                });
+            }
+        }
+
+        links
+    }
+
+    /// Links inside `<!-- -->` comments, whether a block of their own or
+    /// within a paragraph.
+    fn html_comment_links(&self) -> Vec<Link> {
+        let content = self.content();
+        let mut comments =
+            descendants_of_kind(self.tree().block_tree().root_node(), &["html_block"]);
+        for inline_tree in self.tree().inline_trees() {
+            comments.append(&mut nodes_of_kind(inline_tree.root_node(), &["html_tag"]));
+        }
+
+        let mut links = vec![];
+        for comment in comments {
+            let text = &content[comment.byte_range()];
+            let start = match text.trim_start().starts_with("<!--") {
+                true => comment.start_byte() + text.find("<!--").unwrap() + 4,
+                false => continue,
+            };
+            let end = match text.rfind("-->") {
+                Some(end) if comment.start_byte() + end >= start => comment.start_byte() + end,
+                _ => comment.end_byte(),
+            };
+
+            let inner = content[start..end].to_string();
+            let tree = MARKDOWN_PARSER.with_borrow_mut(|p| p.parse(inner.as_bytes(), None));
+            if let Some(tree) = tree {
+                links.append(&mut self.body_links(&tree, inner, start));
+            }
+        }
+
+        links
+    }
+
+    /// Byte ranges of the parsed content taken up by `%% comments %%`, the
+    /// marks included. A comment that is never closed runs to the end of the
+    /// note, as in Obsidian.
+    fn obsidian_comments(&self) -> Vec<Range<usize>> {
+        let root = self.tree().block_tree().root_node();
+        let mut literal = descendants_of_kind(
+            root,
+            &[
+                "fenced_code_block",
+                "indented_code_block",
+                "minus_metadata",
+                "plus_metadata",
+            ],
+        );
+        for inline_tree in self.tree().inline_trees() {
+            literal.append(&mut nodes_of_kind(inline_tree.root_node(), &["code_span"]));
+        }
+
+        let content = self.content();
+        let marks = content
+            .match_indices("%%")
+            .map(|(i, _)| i)
+            .filter(|i| !literal.iter().any(|n| n.byte_range().contains(i)));
+
+        let mut comments = vec![];
+        let mut open = None;
+        for mark in marks {
+            match open.take() {
+                Some(start) => comments.push(start..mark + 2),
+                None => open = Some(mark),
+            }
+        }
+        if let Some(start) = open {
+            comments.push(start..content.len());
+        }
+
+        comments
+    }
+
+    fn frontmatter_links(&self) -> Vec<Link> {
+        let mut links = std::vec![];
+        let mut query_cursor = QueryCursor::new();
+
+        match self.parsed_frontmatter() {
+            Some(frontmatter) => {
+                let matches = query_cursor.matches(
+                    &self.query_cache.frontmatter_links,
+                    frontmatter.tree.root_node(),
+                    frontmatter.text.as_bytes(),
+                );
+
+                let scalar_index = self
+                    .query_cache
+                    .frontmatter_links
+                    .capture_index_for_name("scalar")
+                    .unwrap();
+
+                for found_match in matches {
+                    let node = match found_match
+                        .nodes_for_capture_index(scalar_index)
+                        .collect::<Vec<tree_sitter::Node>>()
+                        .pop()
+                    {
+                        Some(node) => node,
+                        None => continue,
+                    };
+
+                    let scalar = frontmatter.text[node.byte_range()].to_string();
+                    let tree =
+                        MARKDOWN_PARSER.with_borrow_mut(|p| p.parse(scalar.as_bytes(), None));
+                    let tree = match tree {
+                        Some(tree) => tree,
+                        None => continue,
+                    };
+                    let offset = frontmatter.start + node.start_byte();
+                    let mut parsed_links = self.body_links(&tree, scalar.clone(), offset);
+                    links.append(&mut parsed_links);
+                }
+
+                links
+            }
+            None => std::vec![],
+        }
+    }
+
+    /// Inline `#tags` in the note body, without the leading `#`. Nested tags
+    /// such as `#area/subarea` are returned whole. Code is never searched.
+    pub fn inline_tags(&self) -> Vec<String> {
+        if let Some(summary) = &self.summary {
+            return summary.inline_tags.clone();
+        }
+
+        let mut tags = vec![];
+
+        for text in self.inline_texts() {
+            tags.append(&mut scan_tags(&text));
+        }
+
+        tags
+    }
+
+    /// Dataview style `key:: value` fields, from lines starting with one and
+    /// from `[key:: value]` or `(key:: value)` anywhere in the text, keyed
+    /// by field name with every value in document order. Code is never
+    /// searched.
+    pub fn inline_fields(&self) -> HashMap<String, Vec<String>> {
+        let mut fields = HashMap::<String, Vec<String>>::new();
+
+        for text in self.inline_texts() {
+            for line in text.lines() {
+                for (key, value) in scan_fields(line) {
+                    fields.entry(key).or_default().push(value);
+                }
+            }
+        }
+
+        fields
+    }
+
+    /// The frontmatter value of `key`, or failing that its inline fields: a
+    /// `String` for a single field, or a `List` of them.
+    pub fn field(&self, key: &str) -> Option<FrontmatterValue> {
+        if let Some(value) = self.frontmatter_value(key) {
+            return Some(value);
+        }
+
+        let mut values = self.inline_fields().remove(key)?;
+        match values.len() {
+            1 => values.pop().map(FrontmatterValue::String),
+            _ => Some(FrontmatterValue::List(
+                values.into_iter().map(FrontmatterValue::String).collect(),
+            )),
+        }
+    }
+
+    /// The text of each inline tree, which covers every paragraph and heading
+    /// but no code blocks, with code spans blanked out.
+    fn inline_texts(&self) -> Vec<String> {
+        let mut texts = vec![];
+
+        for inline_tree in self.tree().inline_trees() {
+            let root = inline_tree.root_node();
+            let mut text = self.content().as_bytes()[root.byte_range()].to_vec();
+
+            for code in nodes_of_kind(root, &["code_span"]) {
+                let start = code.start_byte() - root.start_byte();
+                let end = code.end_byte() - root.start_byte();
+                text[start..end].fill(b' ');
+            }
+
+            // Blanking whole nodes keeps the buffer valid UTF-8.
+            texts.push(String::from_utf8(text).unwrap());
+        }
+
+        texts
+    }
+
+    /// Words in the note as it reads once rendered: frontmatter, code
+    /// blocks, images and embeds are left out, links count only the text
+    /// they show, and markup such as `**` or `- [ ]` isn't a word.
+    pub fn word_count(&self) -> usize {
+        let mut text = String::new();
+        for inline_tree in self.tree().inline_trees() {
+            visible_text(inline_tree.root_node(), self.content(), &mut text);
+            text.push('\n');
+        }
+
+        text.split_whitespace()
+            .filter(|word| word.chars().any(char::is_alphanumeric))
+            .count()
+    }
+
+    /// Roughly how long the note takes to read at `wpm` words per minute.
+    pub fn reading_time(&self, wpm: usize) -> Duration {
+        Duration::from_secs_f64(self.word_count() as f64 * 60.0 / wpm.max(1) as f64)
+    }
+
+    /// ATX and setext headings, in document order. Code blocks are never
+    /// searched.
+    pub fn headings(&self) -> Vec<Heading> {
+        if let Some(summary) = &self.summary {
+            return summary.headings.clone();
+        }
+
+        let mut headings = vec![];
+        let root = self.tree().block_tree().root_node();
+
+        for heading in descendants_of_kind(root, &["atx_heading", "setext_heading"]) {
+            let mut cursor = heading.walk();
+            let children = heading.children(&mut cursor).collect::<Vec<Node>>();
+            let level = children.iter().find_map(|c| match c.kind() {
+                "setext_h1_underline" => Some(1),
+                "setext_h2_underline" => Some(2),
+                kind => kind
+                    .strip_prefix("atx_h")
+                    .and_then(|k| k.strip_suffix("_marker"))
+                    .and_then(|k| k.parse().ok()),
+            });
+
+            let content = match heading.kind() {
+                "atx_heading" => heading.child_by_field_name("heading_content"),
+                _ => children.iter().find(|c| c.kind() == "paragraph").copied(),
+            };
+            let text = content.map_or("", |c| self.content()[c.byte_range()].trim());
+
+            headings.push(Heading {
+                level: level.unwrap_or(1),
+                text: strip_closing_hashes(text).split_whitespace().join(" "),
+                line: self.line(heading.start_position().row),
+            });
+        }
+
+        headings
+    }
+
+    /// Whether the note has a heading matching `heading`, the anchor of a
+    /// `[[Note#Heading]]` link. For `[[Note#Parent#Child]]` only the last
+    /// heading has to exist.
+    pub fn has_heading(&self, heading: &str) -> bool {
+        let heading = heading_key(heading.rsplit('#').next().unwrap_or(heading));
+
+        self.headings()
+            .iter()
+            .any(|h| heading_key(&h.text) == heading)
+    }
+
+    /// Ids of the blocks marked with a trailing `^id`, without the `^`.
+    pub fn block_ids(&self) -> Vec<String> {
+        if let Some(summary) = &self.summary {
+            return summary.block_ids.clone();
+        }
+
+        let mut ids = vec![];
+
+        for inline_tree in self.tree().inline_trees() {
+            let text = &self.content()[inline_tree.root_node().byte_range()];
+
+            for line in text.lines() {
+                let id = line.trim_end().rsplit(' ').next().unwrap_or("");
+                if let Some(id) = id.strip_prefix('^') {
+                    if !id.is_empty() && id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') {
+                        ids.push(id.to_string());
+                    }
+                }
+            }
+        }
+
+        ids
+    }
+
+    /// Checkbox list items, in document order and including nested items.
+    /// Besides `[ ]` and `[x]`, any single character between the brackets is
+    /// accepted as a custom status. Code blocks are never searched.
+    pub fn tasks(&self) -> Vec<Task> {
+        let mut tasks = vec![];
+        let root = self.tree().block_tree().root_node();
+
+        for item in descendants_of_kind(root, &["list_item"]) {
+            let mut cursor = item.walk();
+            let children = item.children(&mut cursor).collect::<Vec<Node>>();
+            let paragraph = children.iter().find(|c| c.kind() == "paragraph");
+            let text = paragraph.map_or("", |p| self.content()[p.byte_range()].trim());
+
+            let marker = children
+                .iter()
+                .find(|c| c.kind().starts_with("task_list_marker"));
+            let (status, text) = match marker {
+                Some(marker) => {
+                    let marker = &self.content()[marker.byte_range()];
+                    (marker.chars().nth(1).unwrap_or(' '), text)
+                }
+                // The grammar only knows `[ ]` and `[x]`, leaving any other
+                // checkbox at the start of the paragraph.
+                None => match custom_checkbox(text) {
+                    Some((status, text)) => (status, text),
+                    None => continue,
+                },
+            };
+
+            tasks.push(Task {
+                text: text.split_whitespace().join(" "),
+                done: status.eq_ignore_ascii_case(&'x'),
+                status,
+                line: self.line(item.start_position().row),
+            });
+        }
+
+        tasks
+    }
+
+    /// Every node `query` captures in the note's `grammar` trees, in document
+    /// order. `query` has to have been compiled for the same grammar, as
+    /// `Grammar::query` does.
+    pub fn captures(&self, query: &Query, grammar: Grammar) -> Vec<Capture> {
+        // The trees, the text they were parsed from and where that text
+        // starts in the note.
+        let (trees, content, offset) = match grammar {
+            Grammar::Markdown => (
+                vec![self.tree().block_tree().clone()],
+                self.content().to_string(),
+                0,
+            ),
+            Grammar::Inline => (
+                self.tree().inline_trees().to_vec(),
+                self.content().to_string(),
+                0,
+            ),
+            Grammar::Yaml => match self.parsed_frontmatter() {
+                Some(frontmatter) => (
+                    vec![frontmatter.tree.clone()],
+                    frontmatter.text.clone(),
+                    frontmatter.start,
+                ),
+                _ => return vec![],
+            },
+        };
+
+        let mut captures = vec![];
+        for tree in &trees {
+            let mut query_cursor = QueryCursor::new();
+            let matches = query_cursor.captures(query, tree.root_node(), content.as_bytes());
+
+            for (found_match, index) in matches {
+                let capture = found_match.captures[index];
+                captures.push(Capture {
+                    name: query.capture_names()[capture.index as usize].to_string(),
+                    text: content[capture.node.byte_range()].to_string(),
+                    start: self.position(offset + capture.node.start_byte()),
+                    end: self.position(offset + capture.node.end_byte()),
+                });
+            }
+        }
+
+        captures
+    }
+
+    /// Callouts, in document order. A callout nested in another is reported
+    /// as well as the one around it. Code blocks are never searched.
+    pub fn callouts(&self) -> Vec<Callout> {
+        let mut callouts = vec![];
+        let root = self.tree().block_tree().root_node();
+
+        for quote in descendants_of_kind(root, &["block_quote"]) {
+            let mut cursor = quote.walk();
+            let first = quote
+                .named_children(&mut cursor)
+                .find(|c| c.kind() != "block_quote_marker");
+            let paragraph = match first {
+                Some(node) if node.kind() == "paragraph" => node,
+                _ => continue,
+            };
+
+            let text = &self.content()[paragraph.byte_range()];
+            let line = text.lines().next().unwrap_or("");
+            if let Some((kind, title)) = callout_header(line) {
+                callouts.push(Callout {
+                    kind: kind.to_lowercase(),
+                    title: Some(title.to_string()).filter(|t| !t.is_empty()),
+                    line: self.line(quote.start_position().row),
+                });
+            }
+        }
+
+        callouts
+    }
+
+    /// Where `byte` of the parsed content falls in the note's file.
+    fn position(&self, byte: usize) -> Position {
+        let line_starts = self.source().line_starts.get_or_init(|| {
+            std::iter::once(0)
+                .chain(self.content().match_indices('\n').map(|(i, _)| i + 1))
+                .collect()
+        });
+        // The line holding `byte` is the last one starting at or before it.
+        let row = line_starts.partition_point(|&start| start <= byte) - 1;
+
+        Position {
+            line: self.line(row),
+            column: byte - line_starts[row],
+            byte: self.source().skipped.0 + byte,
+        }
+    }
+
+    /// The line of the file a syntax tree row is on.
+    fn line(&self, row: usize) -> usize {
+        self.source().skipped.1 + row
+    }
+
+    /// The bytes of the file holding `link`'s destination, which starts at
+    /// `start` in `content`, if they hold just that. A destination that was
+    /// unescaped or reduced on the way to `Link::destination` can't be
+    /// replaced in place.
+    fn destination_range(
+        &self,
+        link: &Link,
+        content: &str,
+        start: usize,
+        offset: usize,
+    ) -> Option<Range<usize>> {
+        let end = start + link.destination.len();
+        if content.get(start..end) != Some(link.destination.as_str()) {
+            return None;
+        }
+
+        Some(self.position(offset + start).byte..self.position(offset + end).byte)
+    }
+
+    /// Links in `tree`, a parse of `content`. `offset` is where `content`
+    /// starts in the note, so positions are always relative to the file.
+    fn body_links(&self, tree: &MarkdownTree, content: String, offset: usize) -> Vec<Link> {
+        let mut links = std::vec![];
+        let definitions = reference_definitions(tree, &content);
+
+        let query = &self.query_cache.body_query;
+        let destination_index = query.capture_index_for_name("destination").unwrap();
+        let text_index = query.capture_index_for_name("text").unwrap();
+        let label_index = query.capture_index_for_name("label").unwrap();
+        let link_index = query.capture_index_for_name("link").unwrap();
+
+        for inline_tree in tree.inline_trees() {
+            let mut query_cursor = QueryCursor::new();
+            let matches = query_cursor.matches(query, inline_tree.root_node(), content.as_bytes());
+
+            for found_match in matches {
+                // Frontmatter "[[Foo]]" will come in as a wiki_link with just a destination
+                // Frontmatter "[[Foo|bar]]" will come in as a wiki_link with text and destination
+                // Body [[Bar]] and [[dest|name]] come in the same way
+                let capture = |index| found_match.nodes_for_capture_index(index).next();
+                // A query from the query directory may have patterns
+                // without every capture; those matches can't be links.
+                let link_node = match capture(link_index) {
+                    Some(node) => node,
+                    None => continue,
+                };
+
+                if is_in_code(link_node) || has_escaped_close(&content[link_node.byte_range()]) {
+                    continue;
+                }
+
+                let kind = match link_node.kind() {
+                    "wiki_link" => LinkKind::Wiki,
+                    "inline_link" | "image" => LinkKind::Inline,
+                    "uri_autolink" | "email_autolink" => LinkKind::Autolink,
+                    _ => LinkKind::Reference,
+                };
+
+                let mut destination_start = None;
+                let (destination, text) = match kind {
+                    LinkKind::Wiki | LinkKind::Inline => {
+                        // If there is no text, destination is used for both
+                        let dest_node = match capture(destination_index) {
+                            Some(node) => node,
+                            None => continue,
+                        };
+                        let text_node = capture(text_index).unwrap_or(dest_node);
+                        let (destination, start) =
+                            unbracketed(&content[dest_node.byte_range()], dest_node.start_byte());
+                        destination_start = Some(start);
+
+                        (
+                            destination.to_string(),
+                            content[text_node.byte_range()].to_string(),
+                        )
+                    }
+                    LinkKind::Reference => {
+                        // ![[Foo]] wraps a shortcut link; it is picked up as
+                        // an embed below.
+                        if link_node.parent().map(|p| p.kind()) == Some("image_description") {
+                            continue;
+                        }
+
+                        // A [label] without a matching definition is just text
+                        let label_node = match capture(label_index) {
+                            Some(node) => node,
+                            None => continue,
+                        };
+                        let label = normalize_label(&content[label_node.byte_range()]);
+                        let destination = match definitions.get(&label) {
+                            Some((destination, start)) => {
+                                destination_start = Some(*start);
+                                destination.clone()
+                            }
+                            None => continue,
+                        };
+                        let text_node = capture(text_index).unwrap_or(label_node);
+
+                        (destination, content[text_node.byte_range()].to_string())
+                    }
+                    LinkKind::Autolink => {
+                        let url = content[link_node.byte_range()]
+                            .trim_start_matches('<')
+                            .trim_end_matches('>');
+                        let destination = match link_node.kind() {
+                            "email_autolink" => format!("mailto:{}", url),
+                            _ => url.to_string(),
+                        };
+
+                        (destination, url.to_string())
+                    }
+                };
+
+                let is_embed = link_node.kind() == "image";
+
+                let mut link = Link::new(
+                    self.path.clone(),
+                    kind,
+                    destination,
+                    text,
+                    is_embed,
+                    self.position(offset + link_node.start_byte()),
+                    self.position(offset + link_node.end_byte()),
+                );
+                link.raw = content[link_node.byte_range()].to_string();
+                link.destination_range = destination_start
+                    .and_then(|start| self.destination_range(&link, &content, start, offset));
+                links.push(link);
+            }
+
+            // ![[Foo]] and ![[Foo|bar]] parse as an image wrapping a shortcut
+            // link, so the destination and text share one node.
+            let mut query_cursor = QueryCursor::new();
+            let matches = query_cursor.matches(
+                &self.query_cache.embed_query,
+                inline_tree.root_node(),
+                content.as_bytes(),
+            );
+
+            let query = &self.query_cache.embed_query;
+            let embed_index = query.capture_index_for_name("embed").unwrap();
+            let link_index = query.capture_index_for_name("link").unwrap();
+
+            for found_match in matches {
+                let capture = |index| found_match.nodes_for_capture_index(index).next();
+                let (node, link_node) = match (capture(embed_index), capture(link_index)) {
+                    (Some(node), Some(link_node)) => (node, link_node),
+                    _ => continue,
+                };
+
+                if is_in_code(node) || has_escaped_close(&content[link_node.byte_range()]) {
+                    continue;
+                }
+
+                let embed = &content[node.byte_range()];
+                let (destination, text) = embed.split_once('|').unwrap_or((embed, embed));
+
+                let mut link = Link::new(
+                    self.path.clone(),
+                    LinkKind::Wiki,
+                    destination.to_string(),
+                    text.to_string(),
+                    true,
+                    self.position(offset + link_node.start_byte()),
+                    self.position(offset + link_node.end_byte()),
+                );
+                link.raw = content[link_node.byte_range()].to_string();
+                link.destination_range =
+                    self.destination_range(&link, &content, node.start_byte(), offset);
+                links.push(link);
+            }
+        }
+
+        links
+    }
+}
+
+/// Destinations of every `[label]: destination` definition in the document,
+/// with where each starts in `content`, keyed by normalized label. The first
+/// definition of a label wins.
+fn reference_definitions(tree: &MarkdownTree, content: &str) -> HashMap<String, (String, usize)> {
+    let mut definitions = HashMap::new();
+    let root = tree.block_tree().root_node();
+
+    for definition in nodes_of_kind(root, &["link_reference_definition"]) {
+        let mut cursor = definition.walk();
+        let children = definition.children(&mut cursor).collect::<Vec<Node>>();
+        let label = children.iter().find(|c| c.kind() == "link_label");
+        let destination = children.iter().find(|c| c.kind() == "link_destination");
+
+        if let (Some(label), Some(destination)) = (label, destination) {
+            definitions
+                .entry(normalize_label(&content[label.byte_range()]))
+                .or_insert_with(|| {
+                    let (text, start) =
+                        unbracketed(&content[destination.byte_range()], destination.start_byte());
+                    (text.to_string(), start)
+                });
+        }
+    }
+
+    definitions
+}
+
+/// A link destination without the `<...>` that lets it hold spaces, and
+/// where it starts once `start`, where it was written, skips the bracket.
+fn unbracketed(destination: &str, start: usize) -> (&str, usize) {
+    match destination
+        .strip_prefix('<')
+        .and_then(|d| d.strip_suffix('>'))
+    {
+        Some(inner) => (inner, start + 1),
+        None => (destination, start),
+    }
+}
+
+/// Reference labels match case-insensitively, ignoring surrounding brackets
+/// and runs of whitespace.
+fn normalize_label(label: &str) -> String {
+    label
+        .trim_start_matches('[')
+        .trim_end_matches(']')
+        .split_whitespace()
+        .join(" ")
+        .to_lowercase()
+}
+
+/// The strings under `key`, which may hold a single string or an array.
+fn string_list(frontmatter: &serde_json::Value, key: &str) -> Vec<String> {
+    match frontmatter.get(key) {
+        Some(serde_json::Value::String(value)) => vec![value.clone()],
+        Some(serde_json::Value::Array(values)) => values
+            .iter()
+            .filter_map(|v| v.as_str())
+            .map(|v| v.to_string())
+            .collect(),
+        _ => vec![],
+    }
+}
+
+/// Adds the comma separated tags in `scalar`, dropping any leading `#`.
+fn push_tags(tags: &mut Vec<String>, scalar: &str) {
+    for tag in scalar.split(',') {
+        let tag = tag.trim().trim_start_matches('#');
+        if !tag.is_empty() {
+            tags.push(tag.to_string());
+        }
+    }
+}
+
+/// Whether `node` sits inside inline code. Fenced and indented code blocks
+/// never get inline trees, so only code spans can contain a match.
+fn is_in_code(node: Node) -> bool {
+    let mut parent = node.parent();
+
+    while let Some(node) = parent {
+        if node.kind() == "code_span" {
+            return true;
+        }
+        parent = node.parent();
+    }
+
+    false
+}
+
+/// Whether `line` has `open`, then `close` somewhere after it.
+fn has_placeholder(line: &str, open: &str, close: &str) -> bool {
+    match line.find(open) {
+        Some(start) => line[start + open.len()..].contains(close),
+        None => false,
+    }
+}
+
+/// Whether the `]]` closing a wiki link is escaped, as in `[[Foo\]]`, in
+/// which case the grammar still sees a link but Markdown doesn't.
+fn has_escaped_close(link: &str) -> bool {
+    match link.strip_suffix("]]") {
+        Some(inner) => (inner.len() - inner.trim_end_matches('\\').len()) % 2 == 1,
+        None => false,
+    }
+}
+
+/// Appends the text of inline `node` to `text` as a reader would see it,
+/// with links reduced to the text they show and images left out.
+fn visible_text(node: Node, content: &str, text: &mut String) {
+    let child = |kind| {
+        let mut cursor = node.walk();
+        let child = node.children(&mut cursor).find(|c| c.kind() == kind);
+        child
+    };
+
+    match node.kind() {
+        "image" => {}
+        "wiki_link" => {
+            if let Some(shown) = child("link_text").or_else(|| child("link_destination")) {
+                text.push_str(&content[shown.byte_range()]);
+            }
+        }
+        "inline_link" | "full_reference_link" | "collapsed_reference_link" | "shortcut_link" => {
+            if let Some(shown) = child("link_text") {
+                text.push_str(&content[shown.byte_range()]);
+            }
+        }
+        _ => {
+            // Plain text has no nodes of its own, so it is whatever lies
+            // between the children.
+            let mut end = node.start_byte();
+            let mut cursor = node.walk();
+            for child in node.children(&mut cursor) {
+                text.push_str(&content[end..child.start_byte()]);
+                visible_text(child, content, text);
+                end = child.end_byte();
+            }
+            text.push_str(&content[end..node.end_byte()]);
+        }
+    }
+}
+
+/// The outermost nodes below `node` whose kind is in `kinds`.
+fn nodes_of_kind<'t>(node: Node<'t>, kinds: &[&str]) -> Vec<Node<'t>> {
+    let mut nodes = vec![];
+    let mut cursor = node.walk();
+
+    for child in node.children(&mut cursor) {
+        match kinds.contains(&child.kind()) {
+            true => nodes.push(child),
+            false => nodes.append(&mut nodes_of_kind(child, kinds)),
+        }
+    }
+
+    nodes
+}
+
+/// Every node under `node` of one of `kinds`, including those nested inside
+/// another match, in document order.
+fn descendants_of_kind<'t>(node: Node<'t>, kinds: &[&str]) -> Vec<Node<'t>> {
+    let mut nodes = vec![];
+    let mut cursor = node.walk();
+
+    for child in node.children(&mut cursor) {
+        if kinds.contains(&child.kind()) {
+            nodes.push(child);
+        }
+        nodes.append(&mut descendants_of_kind(child, kinds));
+    }
+
+    nodes
+}
+
+/// The type and title of a callout's `[!type]+ Title` first line.
+fn callout_header(line: &str) -> Option<(&str, &str)> {
+    let (kind, rest) = line.trim_start().strip_prefix("[!")?.split_once(']')?;
+    if kind.is_empty() || kind.contains(char::is_whitespace) {
+        return None;
+    }
+
+    // A `+` or `-` makes the callout foldable, open or closed.
+    let title = rest.strip_prefix(['+', '-']).unwrap_or(rest);
+
+    Some((kind, title.trim()))
+}
+
+/// `text` without the optional `###` that may close an ATX heading.
+fn strip_closing_hashes(text: &str) -> &str {
+    let trimmed = text.trim_end_matches('#');
+
+    match trimmed.is_empty() || trimmed.ends_with(' ') {
+        true => trimmed.trim_end(),
+        false => text,
+    }
+}
+
+/// The `key:: value` fields in one line of text: the whole line if it starts
+/// with a key, otherwise each bracketed `[key:: value]` or `(key:: value)`.
+fn scan_fields(line: &str) -> Vec<(String, String)> {
+    let is_key = |key: &str| {
+        !key.is_empty()
+            && key
+                .chars()
+                .all(|c| c.is_alphanumeric() || matches!(c, ' ' | '_' | '-'))
+    };
+
+    if let Some((key, value)) = line.split_once("::") {
+        let key = key.trim();
+        if is_key(key) {
+            return vec![(key.to_string(), value.trim().to_string())];
+        }
+    }
+
+    let mut fields = vec![];
+    for (open, close) in [('[', ']'), ('(', ')')] {
+        for (start, _) in line.match_indices(open) {
+            let inner = &line[start + 1..];
+            let inner = match inner.find(close) {
+                Some(end) => &inner[..end],
+                None => continue,
+            };
+
+            if let Some((key, value)) = inner.split_once("::") {
+                let key = key.trim();
+                if is_key(key) {
+                    fields.push((key.to_string(), value.trim().to_string()));
+                }
+            }
+        }
+    }
+
+    fields
+}
+
+/// Splits a `[c] text` checkbox with a custom status `c` off `text`.
+fn custom_checkbox(text: &str) -> Option<(char, &str)> {
+    let mut chars = text.chars();
+    let (open, status, close) = (chars.next()?, chars.next()?, chars.next()?);
+    let rest = chars.as_str();
+
+    match open == '[' && close == ']' && (rest.is_empty() || rest.starts_with(' ')) {
+        true => Some((status, rest.trim_start())),
+        false => None,
+    }
+}
+
+/// Finds `#tag` tokens in plain text. A tag must start the text or follow
+/// whitespace, so `C#` and `x#y` are not tags, and must not be all digits.
+fn scan_tags(text: &str) -> Vec<String> {
+    let mut tags = vec![];
+    let mut previous: Option<char> = None;
+
+    for (i, c) in text.char_indices() {
+        if c == '#' && previous.is_none_or(char::is_whitespace) {
+            let tag = text[i + 1..]
+                .chars()
+                .take_while(|c| c.is_alphanumeric() || matches!(c, '_' | '-' | '/'))
+                .collect::<String>();
+            let tag = tag.trim_end_matches('/');
+
+            if !tag.is_empty() && !tag.chars().all(|c| c.is_ascii_digit()) {
+                tags.push(tag.to_string());
+            }
+        }
+        previous = Some(c);
+    }
+
+    tags
+}
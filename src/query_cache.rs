@@ -0,0 +1,147 @@
+use crate::DarkstoneError;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::path::Path;
+use tree_sitter::{Language, Parser, Query};
+
+const BODY_QUERY: &str = "
+    (wiki_link (link_destination) @destination (link_text)? @text) @link
+    (inline_link (link_text)? @text (link_destination) @destination) @link
+    (image (image_description)? @text (link_destination) @destination) @link
+    (full_reference_link (link_text) @text (link_label) @label) @link
+    (collapsed_reference_link (link_text) @label) @link
+    (shortcut_link (link_text) @label) @link
+    (uri_autolink) @link
+    (email_autolink) @link
+";
+
+const EMBED_QUERY: &str = "(image (image_description (shortcut_link (link_text) @embed))) @link";
+
+const ALIAS_QUERY: &str = "
+    (document
+      (block_node
+        (block_mapping
+          (block_mapping_pair
+            key: ((flow_node) @key (#eq? @key \"aliases\"))
+            value: (_) @aliases))))
+";
+
+const FRONTMATTER_LINKS_QUERY: &str = "((double_quote_scalar) @scalar)";
+
+const TAGS_QUERY: &str = "
+    (document
+      (block_node
+        (block_mapping
+          (block_mapping_pair
+            key: ((flow_node) @key (#eq? @key \"tags\"))
+            value: (_) @value))))
+";
+
+/// Compiled tree-sitter queries shared by every note in a vault.
+///
+/// Compiling a query is comparatively expensive, so build one cache and hand
+/// an `Arc` of it to each `Note` rather than recompiling per note.
+#[derive(Debug)]
+pub struct QueryCache {
+    pub(crate) body_query: Query,
+    pub(crate) embed_query: Query,
+    pub(crate) alias_query: Query,
+    pub(crate) frontmatter_links: Query,
+    pub(crate) tags_query: Query,
+    /// Hash of every query's source, so a cache extracted with different
+    /// queries can be told apart.
+    pub(crate) fingerprint: u64,
+}
+
+impl QueryCache {
+    /// Compiles every query, failing if a grammar is incompatible with the
+    /// tree-sitter library or a query no longer matches its grammar. Notes
+    /// rely on this check to parse without further error handling.
+    pub fn new() -> Result<Self, DarkstoneError> {
+        Self::build(None)
+    }
+
+    /// Like `new`, but any of `links.scm`, `embeds.scm`, `aliases.scm`,
+    /// `frontmatter_links.scm` and `tags.scm` found in `dir` replaces the
+    /// built-in query of that name.
+    ///
+    /// A replacement has to define the same captures as the query it
+    /// replaces, e.g. `@link`, `@destination`, `@text` and `@label` for
+    /// `links.scm`, since that is how notes find their way around a match.
+    pub fn from_dir(dir: &Path) -> Result<Self, DarkstoneError> {
+        Self::build(Some(dir))
+    }
+
+    fn build(dir: Option<&Path>) -> Result<Self, DarkstoneError> {
+        // The block grammar has no queries of its own to catch a mismatch.
+        Parser::new().set_language(&tree_sitter_md::language())?;
+
+        let inline = tree_sitter_md::inline_language();
+        let yaml = tree_sitter_yaml::language();
+        let mut hasher = DefaultHasher::new();
+        let mut compile = |name, language, default, captures| {
+            compile(dir, name, language, default, captures, &mut hasher)
+        };
+
+        Ok(Self {
+            body_query: compile(
+                "links",
+                &inline,
+                BODY_QUERY,
+                &["link", "destination", "text", "label"],
+            )?,
+            embed_query: compile("embeds", &inline, EMBED_QUERY, &["link", "embed"])?,
+            alias_query: compile("aliases", &yaml, ALIAS_QUERY, &["aliases"])?,
+            frontmatter_links: compile(
+                "frontmatter_links",
+                &yaml,
+                FRONTMATTER_LINKS_QUERY,
+                &["scalar"],
+            )?,
+            tags_query: compile("tags", &yaml, TAGS_QUERY, &["value"])?,
+            fingerprint: hasher.finish(),
+        })
+    }
+}
+
+/// Compiles `<dir>/<name>.scm` if there is one, and the built-in `default`
+/// otherwise, adding the source compiled to `hasher`.
+fn compile(
+    dir: Option<&Path>,
+    name: &str,
+    language: &Language,
+    default: &str,
+    captures: &[&str],
+    hasher: &mut DefaultHasher,
+) -> Result<Query, DarkstoneError> {
+    let path = match dir {
+        Some(dir) if dir.join(format!("{}.scm", name)).is_file() => {
+            dir.join(format!("{}.scm", name))
+        }
+        _ => {
+            default.hash(hasher);
+            return Ok(Query::new(language, default)?);
+        }
+    };
+
+    let source = match std::fs::read_to_string(&path) {
+        Ok(source) => source,
+        Err(err) => return Err(DarkstoneError::Io(path, err)),
+    };
+    source.hash(hasher);
+    let query = match Query::new(language, &source) {
+        Ok(query) => query,
+        Err(err) => return Err(DarkstoneError::InvalidQuery(name.to_string(), path, err)),
+    };
+
+    match captures
+        .iter()
+        .find(|c| query.capture_index_for_name(c).is_none())
+    {
+        Some(capture) => Err(DarkstoneError::MissingCapture(
+            name.to_string(),
+            path,
+            capture.to_string(),
+        )),
+        None => Ok(query),
+    }
+}
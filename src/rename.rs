@@ -0,0 +1,181 @@
+use crate::link::{percent_decode, target_name};
+use crate::note::BOM;
+use crate::resolver::normalize;
+use crate::{DarkstoneError, Link, LinkKind, Vault};
+use itertools::Itertools;
+use std::collections::HashMap;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+/// One replacement within a file.
+#[derive(Clone, Debug)]
+pub struct Edit {
+    pub path: PathBuf,
+    /// Zero-based line of the link being edited.
+    pub line: usize,
+    /// Byte range of the text to replace.
+    pub range: Range<usize>,
+    /// The text currently in `range`.
+    pub original: String,
+    pub replacement: String,
+}
+
+/// Everything renaming a note involves: moving its file, and pointing every
+/// link to it at the new name. Nothing changes on disk until `apply`.
+#[derive(Clone, Debug)]
+pub struct Rename {
+    pub from: PathBuf,
+    pub to: PathBuf,
+    pub edits: Vec<Edit>,
+}
+
+impl Rename {
+    /// Plans renaming the note `note` resolves to as `new_name`, within its
+    /// current folder.
+    ///
+    /// Links that reach the note by name or path have just their destination
+    /// rewritten, so display text, anchors, folders, any note extension and
+    /// `%20` style escapes are kept. For reference-style links that is the
+    /// destination in their `[label]: destination` definition, rewritten
+    /// once however many links use it. Links through one of the note's
+    /// aliases keep working as they are and are left alone.
+    pub fn plan(vault: &Vault, note: &str, new_name: &str) -> Result<Self, DarkstoneError> {
+        let index = vault.index();
+        let target = match index.resolve(note) {
+            Some(target) => target,
+            None => return Err(DarkstoneError::NoSuchNote(note.to_string())),
+        };
+
+        if new_name.trim().is_empty() || new_name.contains(['/', '\\']) {
+            return Err(DarkstoneError::InvalidName(new_name.to_string()));
+        }
+
+        let from = target.path().to_path_buf();
+        let mut file_name = new_name.to_string();
+        if let Some(extension) = from.extension() {
+            file_name = format!("{}.{}", file_name, extension.to_string_lossy());
+        }
+        let to = from.with_file_name(file_name);
+        if to.exists() && !same_file(&from, &to) {
+            return Err(DarkstoneError::Exists(to));
+        }
+
+        let old_name = normalize(&target.name());
+        let links = vault
+            .links()
+            .into_iter()
+            .filter(|l| !l.destination.is_empty())
+            .filter(|l| normalize(&percent_decode(target_name(&l.destination))) == old_name)
+            .filter(|l| index.resolve_link(l).map(|n| n.path()) == Some(target.path()))
+            .collect::<Vec<Link>>();
+
+        let mut contents = HashMap::new();
+        let mut edits = vec![];
+        for link in links {
+            let content = match contents.get(&link.source) {
+                Some(content) => content,
+                None => {
+                    let content = match std::fs::read_to_string(&link.source) {
+                        Ok(content) => content,
+                        Err(err) => return Err(DarkstoneError::Io(link.source.clone(), err)),
+                    };
+                    let content = without_bom(&content).1.to_string();
+                    contents.entry(link.source.clone()).or_insert(content)
+                }
+            };
+
+            // Autolinks are never to notes, and a destination that was
+            // unescaped on the way to `Link::destination` has no range.
+            let range = link
+                .destination_range
+                .clone()
+                .filter(|r| content.get(r.clone()) == Some(link.destination.as_str()));
+            if let Some(range) = range {
+                edits.push(Edit {
+                    path: link.source.clone(),
+                    // A reference link's range is on its definition's line.
+                    line: content[..range.start].matches('\n').count(),
+                    original: content[range.clone()].to_string(),
+                    replacement: renamed(&link, new_name),
+                    range,
+                });
+            }
+        }
+
+        edits.sort_by(|a, b| a.path.cmp(&b.path).then(a.range.start.cmp(&b.range.start)));
+        // Reference links sharing a definition share its range too.
+        edits.dedup_by(|a, b| a.path == b.path && a.range == b.range);
+
+        Ok(Self { from, to, edits })
+    }
+
+    /// Rewrites the links, then renames the file. Fails without touching a
+    /// file if its links have moved since the plan was made.
+    pub fn apply(&self) -> Result<(), DarkstoneError> {
+        for (path, edits) in &self.edits.iter().chunk_by(|e| &e.path) {
+            let file = match std::fs::read_to_string(path) {
+                Ok(file) => file,
+                Err(err) => return Err(DarkstoneError::Io(path.clone(), err)),
+            };
+            // Link positions don't count the byte order mark, but it is put
+            // back when the file is written.
+            let (bom, content) = without_bom(&file);
+            let mut content = content.to_string();
+
+            // Working back from the end keeps earlier ranges valid.
+            let edits = edits.collect::<Vec<&Edit>>();
+            for edit in edits.iter().rev() {
+                if content.get(edit.range.clone()) != Some(edit.original.as_str()) {
+                    return Err(DarkstoneError::Changed(path.clone()));
+                }
+                content.replace_range(edit.range.clone(), &edit.replacement);
+            }
+
+            if let Err(err) = std::fs::write(path, format!("{}{}", bom, content)) {
+                return Err(DarkstoneError::Io(path.clone(), err));
+            }
+        }
+
+        match std::fs::rename(&self.from, &self.to) {
+            Ok(()) => Ok(()),
+            Err(err) => Err(DarkstoneError::Io(self.from.clone(), err)),
+        }
+    }
+}
+
+/// Any byte order mark `file` starts with, and the rest of it.
+fn without_bom(file: &str) -> (&str, &str) {
+    match file.strip_prefix(BOM) {
+        Some(content) => (&file[..BOM.len_utf8()], content),
+        None => ("", file),
+    }
+}
+
+/// The destination of `link` once its note is called `new_name`.
+fn renamed(link: &Link, new_name: &str) -> String {
+    let (folder, file) = match link.destination.rsplit_once('/') {
+        Some((folder, file)) => (format!("{}/", folder), file),
+        None => (String::new(), link.destination.as_str()),
+    };
+    let extension = &file[target_name(file).len()..];
+
+    // A bare space would end a markdown link's destination, unless it is in
+    // `<...>`, which is the only way it would already hold one.
+    let bare = matches!(link.kind, LinkKind::Inline | LinkKind::Reference)
+        && !link.destination.contains(' ');
+    let name = match bare || file.contains("%20") {
+        true => new_name.replace(' ', "%20"),
+        false => new_name.to_string(),
+    };
+
+    format!("{}{}{}", folder, name, extension)
+}
+
+/// Whether two paths are the same file, as they are when a rename only
+/// changes the case of a name on a case-insensitive file system.
+fn same_file(a: &Path, b: &Path) -> bool {
+    match (std::fs::canonicalize(a), std::fs::canonicalize(b)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => false,
+    }
+}
@@ -0,0 +1,156 @@
+use crate::link::{nfc, without_extension};
+use crate::vault::root_of;
+use crate::{Link, Note};
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// Lookup tables for resolving link destinations to notes the way Obsidian
+/// does. Building one walks every note, so build it once per batch of lookups.
+pub(crate) struct Resolver<'a> {
+    /// Each note's path relative to its vault root, `/` separated and
+    /// normalized, keyed by its full path.
+    paths: HashMap<&'a Path, String>,
+    by_path: HashMap<String, &'a Note>,
+    by_name: HashMap<String, Vec<&'a Note>>,
+    by_alias: HashMap<String, Vec<&'a Note>>,
+}
+
+impl<'a> Resolver<'a> {
+    pub(crate) fn new(roots: &[PathBuf], notes: &'a [Note]) -> Self {
+        let mut paths = HashMap::new();
+        let mut by_path = HashMap::new();
+        let mut by_name: HashMap<String, Vec<&'a Note>> = HashMap::new();
+        let mut by_alias: HashMap<String, Vec<&'a Note>> = HashMap::new();
+
+        for note in notes {
+            let relative = match root_of(roots, note.path()) {
+                Some(root) => note.path().strip_prefix(root).unwrap_or(note.path()),
+                None => note.path(),
+            };
+            // Links always separate folders with `/`, whatever the OS does.
+            let relative = relative
+                .with_extension("")
+                .components()
+                .map(|c| c.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/");
+            let relative = normalize(&relative);
+            by_path.insert(relative.clone(), note);
+            paths.insert(note.path(), relative);
+
+            by_name
+                .entry(normalize(&note.name()))
+                .or_default()
+                .push(note);
+            for alias in note.aliases() {
+                by_alias.entry(normalize(&alias)).or_default().push(note);
+            }
+        }
+
+        for candidates in by_name.values_mut().chain(by_alias.values_mut()) {
+            candidates.sort_by(|a, b| closest_first(a.path(), b.path()));
+        }
+
+        Self {
+            paths,
+            by_path,
+            by_name,
+            by_alias,
+        }
+    }
+
+    /// Every note `destination` could refer to, best match first. An exact
+    /// vault-relative path beats the end of one, such as `Projects/Roadmap`
+    /// for `Work/Projects/Roadmap.md`, which beats a basename, which beats
+    /// an alias.
+    pub(crate) fn resolve_all(&self, destination: &str) -> Vec<&'a Note> {
+        let destination = normalize(destination.strip_prefix('/').unwrap_or(destination));
+
+        if let Some(note) = self.by_path.get(&destination) {
+            return vec![note];
+        }
+        if let Some((_, name)) = destination.rsplit_once('/') {
+            let suffix = format!("/{}", destination);
+            let notes = self
+                .by_name
+                .get(name)
+                .into_iter()
+                .flatten()
+                .filter(|n| self.paths[n.path()].ends_with(&suffix))
+                .copied()
+                .collect::<Vec<&Note>>();
+            if !notes.is_empty() {
+                return notes;
+            }
+        }
+        if let Some(notes) = self.by_name.get(&destination) {
+            return notes.clone();
+        }
+
+        self.by_alias.get(&destination).cloned().unwrap_or_default()
+    }
+
+    /// The notes with `alias` among their aliases, best match first.
+    pub(crate) fn alias_owners(&self, alias: &str) -> &[&'a Note] {
+        match self.by_alias.get(&normalize(alias)) {
+            Some(notes) => notes,
+            None => &[],
+        }
+    }
+
+    pub(crate) fn resolve(&self, destination: &str) -> Option<&'a Note> {
+        self.resolve_all(destination).into_iter().next()
+    }
+
+    /// The note `link` points at. External links never resolve.
+    pub(crate) fn resolve_link(&self, link: &Link) -> Option<&'a Note> {
+        if link.is_external() {
+            return None;
+        }
+        let destination = link.decoded_destination();
+
+        match destination.starts_with("./") || destination.starts_with("../") {
+            true => self.resolve_relative(&link.source, &destination),
+            false => self.resolve(&destination),
+        }
+    }
+
+    /// The note at `destination`, a path like `../Projects/Roadmap.md`
+    /// relative to the folder of the note at `source`. Paths leaving the
+    /// vault don't resolve.
+    fn resolve_relative(&self, source: &Path, destination: &str) -> Option<&'a Note> {
+        let source = self.paths.get(source)?;
+        let mut path = source.split('/').collect::<Vec<&str>>();
+        path.pop();
+
+        let destination = normalize(destination);
+        for part in destination.split('/') {
+            match part {
+                "." | "" => {}
+                ".." => {
+                    path.pop()?;
+                }
+                part => path.push(part),
+            }
+        }
+
+        self.by_path.get(&path.join("/")).copied()
+    }
+}
+
+/// Orders note paths the way Obsidian prefers them when several notes share
+/// a name: closest to the vault root first, then shortest, then by path.
+pub(crate) fn closest_first(a: &Path, b: &Path) -> Ordering {
+    a.components()
+        .count()
+        .cmp(&b.components().count())
+        .then_with(|| a.as_os_str().len().cmp(&b.as_os_str().len()))
+        .then_with(|| a.cmp(b))
+}
+
+/// Reduces a link destination or note name to the form used for matching:
+/// composed, lowercase, without a note extension.
+pub(crate) fn normalize(name: &str) -> String {
+    nfc(without_extension(name)).to_lowercase()
+}
@@ -0,0 +1,29 @@
+use serde::Serialize;
+
+/// Summary counts for a vault, from `Vault::stats`.
+#[derive(Clone, Debug, Serialize)]
+pub struct Stats {
+    pub notes: usize,
+    /// Every link written in a note, wherever it points.
+    pub outbound_links: usize,
+    /// Links that resolve to a note in the vault.
+    pub resolved_links: usize,
+    /// Links to URLs of any scheme, as `Link::is_external` tells them apart.
+    pub external_links: usize,
+    pub broken_links: usize,
+    pub unique_targets: usize,
+    pub orphans: usize,
+    pub links_per_note: f64,
+}
+
+/// Counts for the notes in one top-level folder of a vault, from
+/// `Vault::folder_stats`.
+#[derive(Clone, Debug, Serialize)]
+pub struct FolderStats {
+    /// The folder's name, or empty for the notes at the vault root.
+    pub folder: String,
+    pub notes: usize,
+    /// Every link written in the folder's notes, wherever it points.
+    pub links: usize,
+    pub orphans: usize,
+}
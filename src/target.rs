@@ -0,0 +1,40 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// An entry of `Note::targets`: a name the note goes by, or the destination
+/// of one of its links.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum Target {
+    /// The note's own name, or where a link points.
+    Note(String),
+    /// One of the note's aliases, with the name of the note it stands for.
+    Alias { note: String, alias: String },
+}
+
+impl Target {
+    /// The note this is, or the one it is an alias of.
+    pub fn note(&self) -> &str {
+        match self {
+            Target::Note(note) => note,
+            Target::Alias { note, .. } => note,
+        }
+    }
+
+    pub fn alias(&self) -> Option<&str> {
+        match self {
+            Target::Note(_) => None,
+            Target::Alias { alias, .. } => Some(alias),
+        }
+    }
+}
+
+/// A note as its name, and an alias as `note|alias`, the way the target
+/// list has always been printed.
+impl fmt::Display for Target {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Target::Note(note) => write!(f, "{}", note),
+            Target::Alias { note, alias } => write!(f, "{}|{}", note, alias),
+        }
+    }
+}
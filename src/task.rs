@@ -0,0 +1,21 @@
+/// A `- [ ] task` list item.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Task {
+    /// The item's text, without the list marker or checkbox.
+    pub text: String,
+    /// Set for `[x]` and `[X]`.
+    pub done: bool,
+    /// The character between the brackets, e.g. `' '`, `'x'`, or one of
+    /// Obsidian's custom states such as `'/'` for in progress or `'-'` for
+    /// cancelled.
+    pub status: char,
+    /// Zero-based line the item starts on.
+    pub line: usize,
+}
+
+impl Task {
+    /// Whether the task still needs doing: neither done nor cancelled.
+    pub fn is_open(&self) -> bool {
+        !self.done && self.status != '-'
+    }
+}
@@ -0,0 +1,1170 @@
+use crate::cache::Cache;
+use crate::link::{nfc, percent_decode, target_name};
+use crate::resolver::{closest_first, normalize, Resolver};
+use crate::{
+    Attachment, DarkstoneError, DateRange, FolderStats, Link, Note, QueryCache, Stats, Target,
+    VaultIndex,
+};
+use ignore::WalkBuilder;
+use indicatif::ProgressBar;
+use itertools::Itertools;
+use rayon::prelude::*;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, Instant};
+use tracing::{debug, info, trace};
+
+/// Controls which files under the vault root are loaded as notes.
+///
+/// A file is loaded when its extension is one of `extensions` and no
+/// `exclude` pattern matches its path relative to the vault root, or the
+/// relative path of one of its parent directories. Exclusion always wins, so
+/// `Templates` skips every file under `Templates/` whatever its extension.
+///
+/// With `respect_ignore`, hidden files and anything matched by `.gitignore`
+/// or `.ignore` files are skipped as well.
+#[derive(Clone, Debug)]
+pub struct ScanOptions {
+    /// Extensions without the leading dot, e.g. `md`, compared ignoring
+    /// case. `.mdx` files are read with the markdown grammar like any other
+    /// note, so JSX in them is treated as HTML or plain text.
+    pub extensions: Vec<String>,
+    /// Matched against each path relative to its vault root, so characters
+    /// like `[` or `*` in the root's own path are never taken as wildcards.
+    pub exclude: Vec<glob::Pattern>,
+    pub respect_ignore: bool,
+    /// How many folders deep to look for notes, with 1 meaning only files
+    /// directly in the vault root. `None` looks everywhere.
+    pub max_depth: Option<usize>,
+    /// Load files containing invalid UTF-8 with the bad bytes replaced,
+    /// rather than reporting them in `Vault::errors`.
+    pub lossy: bool,
+    /// Also take links from inside `<!-- -->` and `%% %%` comments, which are
+    /// otherwise ignored. See `Note::with_comments`.
+    pub include_comments: bool,
+    /// Directory to cache each note's links, aliases and tags in. Notes whose
+    /// size and modification time match the cache aren't parsed again.
+    pub cache_dir: Option<PathBuf>,
+    /// Drop each note's content and syntax tree as soon as its summary has
+    /// been extracted, so memory use no longer grows with the size of the
+    /// vault's files. See `Note::summarized`.
+    pub summarize: bool,
+    /// Daily notes from outside this range are skipped, as is every other
+    /// note if it only takes daily notes. Links to skipped notes don't
+    /// resolve.
+    pub dates: DateRange,
+    /// Keep template notes in the vault. Otherwise notes in a folder named
+    /// `template_folder`, or that `Note::is_template`, are set aside in
+    /// `Vault::templates`, so their placeholder links don't count.
+    pub include_templates: bool,
+    pub template_folder: String,
+    /// Advanced once for every file scanned. A bar drawing to a terminal
+    /// shows the count and rate as the vault loads; a hidden one does
+    /// nothing.
+    pub progress: ProgressBar,
+}
+
+impl ScanOptions {
+    /// Adds `pattern` to `exclude`, failing if it isn't a valid glob.
+    pub fn exclude_glob(&mut self, pattern: &str) -> Result<(), DarkstoneError> {
+        self.exclude.push(glob::Pattern::new(pattern)?);
+
+        Ok(())
+    }
+
+    fn is_excluded(&self, relative: &Path) -> bool {
+        relative
+            .ancestors()
+            .filter(|p| !p.as_os_str().is_empty())
+            .any(|p| self.exclude.iter().any(|pattern| pattern.matches_path(p)))
+    }
+
+    /// Every entry under `root` that isn't excluded or ignored.
+    fn walk(&self, root: &Path) -> ignore::Walk {
+        self.walk_towards(root, root)
+    }
+
+    /// Like `walk`, but only going into the folders on the way to `path`,
+    /// so whether `path` would be scanned can be found out without walking
+    /// the whole vault. The same rules apply either way.
+    fn walk_towards(&self, root: &Path, path: &Path) -> ignore::Walk {
+        let options = self.clone();
+        let filter_root = root.to_path_buf();
+        let path = path.to_path_buf();
+
+        // Following links lets a vault pull in symlinked folders; the walker
+        // reports symlink loops as errors instead of descending forever.
+        WalkBuilder::new(root)
+            .standard_filters(self.respect_ignore)
+            .require_git(false)
+            .follow_links(true)
+            .max_depth(self.max_depth)
+            .filter_entry(move |entry| {
+                let relative = entry
+                    .path()
+                    .strip_prefix(&filter_root)
+                    .unwrap_or(entry.path());
+                !options.is_excluded(relative)
+                    && (path.starts_with(entry.path()) || entry.path().starts_with(&path))
+            })
+            .build()
+    }
+
+    /// `note`, just loaded, set up the way these options ask for.
+    fn prepared(&self, note: Note) -> Note {
+        let note = note.with_comments(self.include_comments);
+
+        match self.summarize {
+            true => note.summarized(),
+            false => note,
+        }
+    }
+
+    fn is_in_range(&self, path: &Path) -> bool {
+        match path.file_stem() {
+            Some(name) => self.dates.contains(&name.to_string_lossy()),
+            None => true,
+        }
+    }
+
+    /// Whether `note`, at `relative` to its vault root, is a template these
+    /// options set aside.
+    fn is_set_aside(&self, note: &Note, relative: &Path) -> bool {
+        if self.include_templates {
+            return false;
+        }
+
+        let in_folder = relative.parent().is_some_and(|folder| {
+            folder
+                .components()
+                .any(|c| c.as_os_str().eq_ignore_ascii_case(&self.template_folder))
+        });
+
+        in_folder || note.is_template()
+    }
+
+    pub(crate) fn has_extension(&self, path: &Path) -> bool {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some(ext) => self
+                .extensions
+                .iter()
+                .any(|e| e.trim_start_matches('.').eq_ignore_ascii_case(ext)),
+            None => false,
+        }
+    }
+}
+
+impl Default for ScanOptions {
+    /// Markdown files, as `.md`, `.markdown` or `.mdx`, skipping Obsidian's
+    /// config folder and trash.
+    fn default() -> Self {
+        Self {
+            extensions: vec!["md".to_string(), "markdown".to_string(), "mdx".to_string()],
+            exclude: vec![
+                glob::Pattern::new(".obsidian").unwrap(),
+                glob::Pattern::new(".trash").unwrap(),
+            ],
+            respect_ignore: true,
+            max_depth: None,
+            lossy: false,
+            include_comments: false,
+            cache_dir: None,
+            summarize: false,
+            dates: DateRange::default(),
+            include_templates: false,
+            template_folder: "Templates".to_string(),
+            progress: ProgressBar::hidden(),
+        }
+    }
+}
+
+/// Which way links are followed when walking the link graph.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Direction {
+    /// From a note to the notes it links to.
+    Out,
+    /// From a note to the notes linking to it.
+    In,
+    Both,
+}
+
+#[derive(Debug)]
+pub struct Vault {
+    roots: Vec<PathBuf>,
+    notes: Vec<Note>,
+    templates: Vec<Note>,
+    errors: Vec<DarkstoneError>,
+    options: ScanOptions,
+    query_cache: Arc<QueryCache>,
+    timings: Timings,
+    lookup: OnceLock<Lookup>,
+}
+
+/// Where each note is in `Vault::notes`, by path and by name, built the
+/// first time a note is looked up and dropped whenever the notes change.
+#[derive(Debug)]
+struct Lookup {
+    by_path: HashMap<PathBuf, usize>,
+    /// Normalized names and aliases, each to the note closest to the root
+    /// that has it.
+    by_name: HashMap<String, usize>,
+    by_alias: HashMap<String, usize>,
+}
+
+impl Lookup {
+    fn new(notes: &[Note]) -> Self {
+        let mut by_path = HashMap::new();
+        let mut by_name = HashMap::new();
+        let mut by_alias = HashMap::new();
+
+        // Each name and alias goes to the first note in this order that has
+        // it.
+        let order = (0..notes.len())
+            .sorted_by(|&a, &b| closest_first(notes[a].path(), notes[b].path()))
+            .collect::<Vec<usize>>();
+        for i in order {
+            let note = &notes[i];
+            by_path.insert(note.path().to_path_buf(), i);
+            by_name.entry(normalize(&note.name())).or_insert(i);
+            for alias in note.aliases() {
+                by_alias.entry(normalize(&alias)).or_insert(i);
+            }
+        }
+
+        Self {
+            by_path,
+            by_name,
+            by_alias,
+        }
+    }
+}
+
+/// How long each phase of loading a vault took, summed over its roots.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Timings {
+    /// Walking the vault for note files.
+    pub walk: Duration,
+    /// Reading and parsing the notes, or taking them from the cache.
+    pub parse: Duration,
+    /// Extracting links, tags and the rest from the parsed notes. Zero unless
+    /// `ScanOptions::summarize` is set, as notes are otherwise queried when
+    /// first asked for them.
+    pub extract: Duration,
+}
+
+impl Vault {
+    /// Loads every note under `path`. Fails only if the vault itself can't be
+    /// read; notes that fail to load are collected in `errors` instead.
+    pub fn new(path: PathBuf) -> Result<Self, DarkstoneError> {
+        Self::with_query_cache(path, Arc::new(QueryCache::new()?))
+    }
+
+    /// Like `new`, but reuses an already compiled `QueryCache`.
+    pub fn with_query_cache(
+        path: PathBuf,
+        query_cache: Arc<QueryCache>,
+    ) -> Result<Self, DarkstoneError> {
+        Self::with_options(path, &ScanOptions::default(), query_cache)
+    }
+
+    pub fn with_options(
+        path: PathBuf,
+        options: &ScanOptions,
+        query_cache: Arc<QueryCache>,
+    ) -> Result<Self, DarkstoneError> {
+        Self::with_roots(vec![path], options, query_cache)
+    }
+
+    /// Loads the notes under every one of `paths` into one vault, as if they
+    /// were folders of a single vault: links between them resolve, and
+    /// `relative_path` starts each path with the folder name of its root so
+    /// the notes of each can be told apart. A root given twice is loaded
+    /// once.
+    pub fn with_roots(
+        paths: Vec<PathBuf>,
+        options: &ScanOptions,
+        query_cache: Arc<QueryCache>,
+    ) -> Result<Self, DarkstoneError> {
+        let mut roots = Vec::<PathBuf>::new();
+        for path in paths {
+            let canonical = std::fs::canonicalize(&path).ok();
+            if canonical.is_none()
+                || !roots
+                    .iter()
+                    .any(|r| std::fs::canonicalize(r).ok() == canonical)
+            {
+                roots.push(path);
+            }
+        }
+
+        let mut notes = vec![];
+        let mut errors = vec![];
+        let mut timings = Timings::default();
+        for root in &roots {
+            let (mut root_notes, mut root_errors) =
+                scan(root, options, &query_cache, &mut timings)?;
+            notes.append(&mut root_notes);
+            errors.append(&mut root_errors);
+        }
+
+        // `par_bridge` hands out files in whatever order threads finish, so
+        // restore a stable order for everything computed from the notes.
+        notes.sort_by(|a, b| a.path().cmp(b.path()));
+        // A root inside another one finds some notes twice.
+        notes.dedup_by(|a, b| a.path() == b.path());
+        errors.sort_by(|a, b| a.path().cmp(&b.path()));
+
+        let mut vault = Self {
+            roots,
+            notes: vec![],
+            templates: vec![],
+            errors,
+            options: options.clone(),
+            query_cache,
+            timings,
+            lookup: OnceLock::new(),
+        };
+        (vault.templates, vault.notes) = notes
+            .into_iter()
+            .partition(|n| options.is_set_aside(n, vault.relative_to_root(n.path())));
+
+        Ok(vault)
+    }
+
+    /// A vault of notes that were loaded some other way. It has no root, so
+    /// `relative_path` leaves paths as they are, and notes reloaded later get
+    /// the default `ScanOptions`.
+    pub fn from_notes(notes: Vec<Note>) -> Result<Self, DarkstoneError> {
+        let query_cache = match notes.first() {
+            Some(note) => note.query_cache().clone(),
+            None => Arc::new(QueryCache::new()?),
+        };
+
+        let mut vault = Self {
+            roots: vec![],
+            notes: vec![],
+            templates: vec![],
+            errors: vec![],
+            options: ScanOptions::default(),
+            query_cache,
+            timings: Timings::default(),
+            lookup: OnceLock::new(),
+        };
+        vault.add_notes(notes);
+
+        Ok(vault)
+    }
+
+    /// Adds the notes, templates, errors and roots of `other` to the vault. A note both
+    /// vaults have, by path, is kept only once, as it is in this vault.
+    pub fn extend(&mut self, other: Vault) {
+        for root in other.roots {
+            if !self.roots.contains(&root) {
+                self.roots.push(root);
+            }
+        }
+
+        let failed = self
+            .errors
+            .iter()
+            .filter_map(|e| e.path().map(|p| p.to_path_buf()))
+            .collect::<HashSet<PathBuf>>();
+        self.errors.extend(
+            other
+                .errors
+                .into_iter()
+                .filter(|e| e.path().is_none_or(|p| !failed.contains(p))),
+        );
+        self.errors.sort_by(|a, b| a.path().cmp(&b.path()));
+
+        self.add_notes(other.notes);
+        self.timings.walk += other.timings.walk;
+        self.timings.parse += other.timings.parse;
+        self.timings.extract += other.timings.extract;
+
+        for template in other.templates {
+            if let Err(index) = self
+                .templates
+                .binary_search_by(|t| t.path().cmp(template.path()))
+            {
+                self.templates.insert(index, template);
+            }
+        }
+    }
+
+    /// Merges `notes` into the vault's sorted notes, skipping any whose path
+    /// is already there.
+    fn add_notes(&mut self, notes: Vec<Note>) {
+        let paths = self
+            .notes
+            .iter()
+            .map(|n| n.path().to_path_buf())
+            .collect::<HashSet<PathBuf>>();
+
+        self.notes
+            .extend(notes.into_iter().filter(|n| !paths.contains(n.path())));
+        // Stable, so the first of several notes with one path is kept.
+        self.notes.sort_by(|a, b| a.path().cmp(b.path()));
+        self.notes.dedup_by(|a, b| a.path() == b.path());
+        self.lookup = OnceLock::new();
+    }
+
+    /// How long loading the vault took. Notes loaded with `reload` aren't
+    /// counted.
+    pub fn timings(&self) -> Timings {
+        self.timings
+    }
+
+    /// Deletes the cache kept in `cache_dir` for the vault at `root`, so the
+    /// next scan parses every note.
+    pub fn clear_cache(root: &Path, cache_dir: &Path) -> Result<(), DarkstoneError> {
+        let file = Cache::file(cache_dir, root);
+
+        match std::fs::remove_file(&file) {
+            Err(err) if err.kind() != std::io::ErrorKind::NotFound => {
+                Err(DarkstoneError::Io(file, err))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Brings the vault up to date after `path` changed on disk, without
+    /// rescanning anything else. `path` may be a note that was created,
+    /// modified or deleted, or a deleted folder. Absolute paths, as reported
+    /// by file watchers, are mapped onto the vault root. Returns whether any
+    /// note was added, replaced or removed.
+    pub fn reload(&mut self, path: &Path) -> bool {
+        // The deepest root, as in `root_of`, leaves the least behind.
+        let canonical = self
+            .roots
+            .iter()
+            .filter_map(|root| {
+                let relative = path.strip_prefix(std::fs::canonicalize(root).ok()?).ok()?;
+                Some((root, relative))
+            })
+            .min_by_key(|(_, relative)| relative.components().count())
+            .map(|(root, relative)| root.join(relative));
+        let path = match canonical {
+            Some(canonical) if path.is_absolute() => canonical,
+            _ => path.to_path_buf(),
+        };
+
+        self.lookup = OnceLock::new();
+        let before = self.notes.len() + self.templates.len();
+        self.notes.retain(|n| !n.path().starts_with(&path));
+        self.templates.retain(|n| !n.path().starts_with(&path));
+        self.errors
+            .retain(|e| e.path().is_none_or(|p| !p.starts_with(&path)));
+        let mut changed = self.notes.len() + self.templates.len() != before;
+
+        let root = match self.root_of(&path) {
+            Some(root) => root,
+            None => return changed,
+        };
+        // The walk decides, so ignore files, exclusions and `max_depth` keep
+        // out the same notes they kept out of the first scan.
+        let scanned = self
+            .options
+            .walk_towards(root, &path)
+            .filter_map(|entry| entry.ok())
+            .any(|entry| entry.path() == path && entry.file_type().is_some_and(|t| t.is_file()));
+        if !scanned || !self.options.has_extension(&path) || !self.options.is_in_range(&path) {
+            return changed;
+        }
+
+        let relative = self.relative_to_root(&path);
+
+        match Note::load(path.clone(), self.query_cache.clone(), self.options.lossy) {
+            Ok(note) => {
+                let notes = match self.options.is_set_aside(&note, relative) {
+                    true => &mut self.templates,
+                    false => &mut self.notes,
+                };
+                let index = notes.partition_point(|n| n.path() < path.as_path());
+                notes.insert(index, self.options.prepared(note));
+                changed = true;
+            }
+            Err(err) => self.errors.push(err),
+        }
+
+        changed
+    }
+
+    /// Files that were scanned but could not be loaded as notes.
+    pub fn errors(&self) -> &[DarkstoneError] {
+        &self.errors
+    }
+
+    /// The vault's root, or its first root if it was loaded from several.
+    /// An empty path for a vault built with `from_notes`.
+    pub fn root(&self) -> &Path {
+        match self.roots.first() {
+            Some(root) => root,
+            None => Path::new(""),
+        }
+    }
+
+    pub fn roots(&self) -> &[PathBuf] {
+        &self.roots
+    }
+
+    pub(crate) fn options(&self) -> &ScanOptions {
+        &self.options
+    }
+
+    /// `path` relative to the root it lies under, whichever vault that is.
+    pub(crate) fn relative_to_root<'a>(&self, path: &'a Path) -> &'a Path {
+        match self.root_of(path) {
+            Some(root) => path.strip_prefix(root).unwrap_or(path),
+            None => path,
+        }
+    }
+
+    /// The root `path` lies under, if any.
+    pub fn root_of(&self, path: &Path) -> Option<&Path> {
+        root_of(&self.roots, path)
+    }
+
+    /// `path` relative to the vault root, or unchanged if it lies outside it.
+    /// In a vault with several roots the path keeps the folder name of its
+    /// root, as in `work/Projects/Plan.md`.
+    pub fn relative_path<'a>(&self, path: &'a Path) -> &'a Path {
+        let root = match self.root_of(path) {
+            Some(root) if self.roots.len() > 1 => root.parent().unwrap_or(root),
+            Some(root) => root,
+            None => return path,
+        };
+
+        path.strip_prefix(root).unwrap_or(path)
+    }
+
+    pub fn notes(&self) -> &[Note] {
+        &self.notes
+    }
+
+    /// The note called `name`, or failing that the one with `name` as an
+    /// alias, ignoring case and any note extension as `resolve` does. When
+    /// several notes share a name the one closest to the root is returned.
+    /// Unlike `resolve`, folders aren't looked at, and after the first call
+    /// each lookup takes constant time.
+    pub fn note_by_name(&self, name: &str) -> Option<&Note> {
+        let lookup = self.lookup();
+        let name = normalize(name);
+        let i = lookup
+            .by_name
+            .get(&name)
+            .or_else(|| lookup.by_alias.get(&name))?;
+
+        Some(&self.notes[*i])
+    }
+
+    /// The note whose file is `path`, given as it appears in `Note::path`.
+    pub fn note_by_path(&self, path: &Path) -> Option<&Note> {
+        let i = self.lookup().by_path.get(path)?;
+
+        Some(&self.notes[*i])
+    }
+
+    fn lookup(&self) -> &Lookup {
+        self.lookup.get_or_init(|| Lookup::new(&self.notes))
+    }
+
+    /// Template notes left out of `notes`. Always empty if the vault was
+    /// loaded with `ScanOptions::include_templates`.
+    pub fn templates(&self) -> &[Note] {
+        &self.templates
+    }
+
+    /// Every link in the vault, once per occurrence and with its position.
+    /// See `unique_links` for counting how often notes refer to something.
+    pub fn links(&self) -> Vec<Link> {
+        self.notes.iter().flat_map(|n| n.links()).collect()
+    }
+
+    /// `links` with every link from the same note to the same destination
+    /// collapsed into its first occurrence, paired with the number of
+    /// occurrences. Destinations are compared as written, anchors aside, so
+    /// `[[Project]]` and `[[Project#Goals]]` count together.
+    ///
+    /// Use this to ask how many times a note refers to something, and
+    /// `weighted_edges` to ask how strongly two notes are linked once the
+    /// destinations are resolved.
+    pub fn unique_links(&self) -> Vec<(Link, usize)> {
+        let mut unique = Vec::<(Link, usize)>::new();
+        let mut seen = HashMap::<(PathBuf, String), usize>::new();
+
+        for link in self.links() {
+            let key = (link.source.clone(), link.destination.clone());
+            match seen.get(&key) {
+                Some(&index) => unique[index].1 += 1,
+                None => {
+                    seen.insert(key, unique.len());
+                    unique.push((link, 1));
+                }
+            }
+        }
+
+        unique
+    }
+
+    /// Links that embed their destination rather than referencing it.
+    pub fn embeds(&self) -> Vec<Link> {
+        self.links().into_iter().filter(|l| l.is_embed).collect()
+    }
+
+    /// The `Link::is_external` links that go to `http` and `https` URLs,
+    /// including `<https://...>` autolinks. `mailto:` and other schemes are
+    /// external too, but left out.
+    pub fn external_links(&self) -> Vec<Link> {
+        self.links()
+            .into_iter()
+            .filter(|l| l.is_external() && l.is_url())
+            .collect()
+    }
+
+    /// Links to files other than notes, such as `![[image.png]]` or
+    /// `[diagram](assets/diagram.svg)`, with the file each one refers to.
+    /// Like Obsidian, a destination is looked up relative to the vault root,
+    /// then relative to the linking note, then by file name anywhere in the
+    /// vault, preferring the file closest to the root. `%20` style escapes
+    /// are decoded first.
+    pub fn attachments(&self) -> Vec<Attachment> {
+        self.index().attachments()
+    }
+
+    /// Files in the vault, other than notes, that no link refers to.
+    pub fn unused_attachments(&self) -> Vec<PathBuf> {
+        self.index().unused_attachments()
+    }
+
+    /// Every file in the vault that isn't a note, sorted by path.
+    pub(crate) fn files(&self) -> Vec<PathBuf> {
+        self.roots
+            .iter()
+            .flat_map(|root| self.options.walk(root))
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.file_type().is_some_and(|t| t.is_file()))
+            .filter(|entry| !self.options.has_extension(entry.path()))
+            .map(|entry| entry.into_path())
+            .sorted()
+            .collect()
+    }
+
+    /// Every distinct `Note::targets` entry in the vault.
+    pub fn targets(&self) -> Vec<Target> {
+        self.notes
+            .par_iter()
+            .map(|n| n.targets())
+            .flatten()
+            .collect::<Vec<Target>>()
+            .into_iter()
+            .unique()
+            .collect()
+    }
+
+    /// Every distinct `Note::raw_targets` entry in the vault.
+    pub fn raw_targets(&self) -> Vec<Target> {
+        self.notes
+            .par_iter()
+            .map(|n| n.raw_targets())
+            .flatten()
+            .collect::<Vec<Target>>()
+            .into_iter()
+            .unique()
+            .collect()
+    }
+
+    /// Every distinct tag used in the vault.
+    pub fn tags(&self) -> Vec<String> {
+        self.notes
+            .par_iter()
+            .flat_map(|n| n.tags())
+            .collect::<Vec<String>>()
+            .into_iter()
+            .unique()
+            .collect()
+    }
+
+    /// Number of times each tag occurs across the vault.
+    pub fn tag_counts(&self) -> HashMap<String, usize> {
+        self.notes
+            .par_iter()
+            .flat_map(|n| n.tags())
+            .collect::<Vec<String>>()
+            .into_iter()
+            .counts()
+    }
+
+    /// Number of notes each tag is in. With `rollup`, a note with a nested
+    /// tag such as `project/active` also counts towards `project`, though
+    /// only once however many of its tags are under `project`.
+    pub fn tag_note_counts(&self, rollup: bool) -> HashMap<String, usize> {
+        self.notes
+            .par_iter()
+            .flat_map_iter(|n| {
+                let tags = n.tags().into_iter().flat_map(|tag| match rollup {
+                    true => tag
+                        .match_indices('/')
+                        .map(|(i, _)| tag[..i].to_string())
+                        .chain([tag.clone()])
+                        .collect::<Vec<String>>(),
+                    false => vec![tag],
+                });
+
+                tags.unique().collect::<Vec<String>>()
+            })
+            .collect::<Vec<String>>()
+            .into_iter()
+            .counts()
+    }
+
+    /// For every pair of tags found together in a note, the number of notes
+    /// they are both in. Each pair is in alphabetical order and appears
+    /// once. Sorted by count, most common first, then by pair.
+    pub fn tag_cooccurrence(&self) -> Vec<((String, String), usize)> {
+        self.notes
+            .par_iter()
+            .flat_map_iter(|n| {
+                n.tags()
+                    .into_iter()
+                    .sorted()
+                    .dedup()
+                    .tuple_combinations::<(String, String)>()
+            })
+            .collect::<Vec<(String, String)>>()
+            .into_iter()
+            .counts()
+            .into_iter()
+            .sorted_by(|(a, a_count), (b, b_count)| b_count.cmp(a_count).then_with(|| a.cmp(b)))
+            .collect()
+    }
+
+    /// The note `destination` links to. Matching follows Obsidian: it is
+    /// case-insensitive, ignores a note extension such as `.md`, and accepts
+    /// either the note's path relative to the vault root, the end of that
+    /// path from any folder on, its name, or an alias. Folders are always
+    /// separated with `/`. When several notes share a name the one closest
+    /// to the root wins.
+    pub fn resolve(&self, destination: &str) -> Option<&Note> {
+        self.resolver().resolve(destination)
+    }
+
+    /// The note `link` points at, as `resolve` finds it for the link's
+    /// destination once any `%20` style escapes in a markdown link are
+    /// decoded. A destination starting with `./` or `../` is instead taken
+    /// relative to the folder of the note the link is in. External links
+    /// never resolve.
+    pub fn resolve_link(&self, link: &Link) -> Option<&Note> {
+        self.resolver().resolve_link(link)
+    }
+
+    /// Every note `destination` could refer to, best match first. More than
+    /// one result means the destination is ambiguous.
+    pub fn resolve_all(&self, destination: &str) -> Vec<&Note> {
+        self.resolver().resolve_all(destination)
+    }
+
+    /// Resolves every link in the vault once, for answering many questions
+    /// about how notes link to each other. See `VaultIndex`.
+    pub fn index(&self) -> VaultIndex<'_> {
+        VaultIndex::new(self)
+    }
+
+    /// Distinct `(source, destination)` note pairs joined by at least one
+    /// resolved link.
+    pub fn edges(&self) -> Vec<(&Note, &Note)> {
+        self.index().edges()
+    }
+
+    /// Like `edges`, with the number of links joining each pair.
+    pub fn weighted_edges(&self) -> Vec<(&Note, &Note, usize)> {
+        self.index().weighted_edges()
+    }
+
+    /// Groups of notes connected by links in either direction, largest first,
+    /// with each group sorted by path. A note without any links is a group of
+    /// its own.
+    pub fn components(&self) -> Vec<Vec<&Note>> {
+        let index = self
+            .notes
+            .iter()
+            .enumerate()
+            .map(|(i, n)| (n.path(), i))
+            .collect::<HashMap<&Path, usize>>();
+
+        // Union-find, where each note starts out as its own root.
+        let mut parents = (0..self.notes.len()).collect::<Vec<usize>>();
+
+        for (source, destination) in self.edges() {
+            let a = find_root(&mut parents, index[source.path()]);
+            let b = find_root(&mut parents, index[destination.path()]);
+            parents[a.max(b)] = a.min(b);
+        }
+
+        // Notes are sorted by path, so each group comes out sorted too.
+        let mut components = (0..self.notes.len())
+            .map(|i| (find_root(&mut parents, i), &self.notes[i]))
+            .into_group_map()
+            .into_values()
+            .collect::<Vec<Vec<&Note>>>();
+        components.sort_by(|a, b| {
+            b.len()
+                .cmp(&a.len())
+                .then_with(|| a[0].path().cmp(b[0].path()))
+        });
+
+        components
+    }
+
+    /// Every note within `depth` links of `note`, which may be anything
+    /// `resolve` accepts, with its distance from it: `note` itself at 0, the
+    /// notes it is linked with at 1, and so on, following links the way
+    /// `direction` says. Sorted by distance, then path. An unknown `note` has
+    /// no neighbors.
+    pub fn neighbors(&self, note: &str, depth: usize, direction: Direction) -> Vec<(&Note, usize)> {
+        self.index().neighbors(note, depth, direction)
+    }
+
+    /// The notes on every chain of links that leads back where it started
+    /// within `max_len` notes, like `A -> B -> A`. Each cycle is listed once,
+    /// from its note first by path, and without repeating that note at the
+    /// end. See `VaultIndex::cycles`.
+    pub fn cycles(&self, max_len: usize) -> Vec<Vec<&Note>> {
+        self.index().cycles(max_len)
+    }
+
+    /// PageRank of every note over the link graph, highest first, so a link
+    /// from a well linked note counts for more than one from an obscure note.
+    /// Each linked pair counts once and self-links are ignored. The rank of
+    /// notes without outbound links is spread evenly over every note, so the
+    /// scores always sum to 1. `0.85` and `50` are the usual arguments. Ties
+    /// are broken by path.
+    pub fn pagerank(&self, damping: f64, iterations: usize) -> Vec<(&Note, f64)> {
+        let count = self.notes.len();
+        if count == 0 {
+            return vec![];
+        }
+
+        let index = self
+            .notes
+            .iter()
+            .enumerate()
+            .map(|(i, n)| (n.path(), i))
+            .collect::<HashMap<&Path, usize>>();
+
+        let mut outbound = vec![vec![]; count];
+        for (source, destination) in self.edges() {
+            if source.path() != destination.path() {
+                outbound[index[source.path()]].push(index[destination.path()]);
+            }
+        }
+
+        let mut ranks = vec![1.0 / count as f64; count];
+        for _ in 0..iterations {
+            let dangling = (0..count)
+                .filter(|i| outbound[*i].is_empty())
+                .map(|i| ranks[i])
+                .sum::<f64>();
+            let base = (1.0 - damping + damping * dangling) / count as f64;
+
+            let mut next = vec![base; count];
+            for (source, destinations) in outbound.iter().enumerate() {
+                let share = damping * ranks[source] / destinations.len().max(1) as f64;
+                for destination in destinations {
+                    next[*destination] += share;
+                }
+            }
+            ranks = next;
+        }
+
+        self.notes
+            .iter()
+            .zip(ranks)
+            .sorted_by(|(a_note, a), (b_note, b)| {
+                b.total_cmp(a)
+                    .then_with(|| a_note.path().cmp(b_note.path()))
+            })
+            .collect()
+    }
+
+    /// How many other notes link to each note, keyed by note path, so notes
+    /// sharing a name are counted apart. Links via an alias count towards the
+    /// aliased note; notes nothing links to are left out.
+    pub fn inbound_counts(&self) -> HashMap<PathBuf, usize> {
+        self.index().inbound_counts()
+    }
+
+    /// Notes containing at least one link to `target`, which may be anything
+    /// `resolve` accepts. Each linking note is returned once.
+    pub fn backlinks(&self, target: &str) -> Vec<&Note> {
+        self.index().backlinks(target)
+    }
+
+    /// Notes that `note`, which may be anything `resolve` accepts, links to,
+    /// each once and in the order first linked. Links that don't resolve are
+    /// left out, and an unknown `note` has no outlinks.
+    pub fn outlinks(&self, note: &str) -> Vec<&Note> {
+        self.index().outlinks(note)
+    }
+
+    /// The other direction of `outlinks`: the notes linking to `note`. The
+    /// same as `backlinks`.
+    pub fn inlinks(&self, note: &str) -> Vec<&Note> {
+        self.backlinks(note)
+    }
+
+    /// Links whose destination doesn't `resolve` to a note. External URLs,
+    /// `[[#Heading]]` links within the same note and links to attachments
+    /// that exist, such as `![[diagram.png]]`, are never reported.
+    pub fn broken_links(&self) -> Vec<Link> {
+        self.index().broken_links()
+    }
+
+    /// Note names and aliases close enough to a broken link's `destination`
+    /// that it was probably meant for one of them, closest first and at most
+    /// three. Names are compared the way `resolve` matches them, without
+    /// folders and ignoring case, and only offered when no more than a third
+    /// of their characters, and at least one, need changing.
+    pub fn suggestions(&self, destination: &str) -> Vec<String> {
+        let wanted = normalize(&percent_decode(target_name(destination)));
+        let limit = (wanted.chars().count() / 3).max(1);
+
+        self.notes
+            .iter()
+            .flat_map(|n| std::iter::once(n.name()).chain(n.aliases()))
+            .map(|name| (strsim::levenshtein(&wanted, &normalize(&name)), name))
+            .filter(|(distance, _)| *distance <= limit)
+            .sorted()
+            .map(|(_, name)| name)
+            .unique()
+            .take(3)
+            .collect()
+    }
+
+    /// Links that `resolve` to the note they are in, whether by its name,
+    /// its path or one of its aliases. Links to a `#Heading` or `#^block`
+    /// are left out, since those are a way of getting around a long note.
+    pub fn self_links(&self) -> Vec<Link> {
+        self.index().self_links()
+    }
+
+    /// Links that `resolve` to a note lacking the `#Heading` or `#^block`
+    /// they point at. `[[#Heading]]` links are checked against their own
+    /// note. Headings match as explained in `Note::has_heading`; block ids
+    /// must match exactly.
+    pub fn broken_anchors(&self) -> Vec<Link> {
+        self.index().broken_anchors()
+    }
+
+    /// Notes that neither link anywhere nor are linked to by another note,
+    /// whether by name or by alias.
+    pub fn orphans(&self) -> Vec<&Note> {
+        self.index().orphans()
+    }
+
+    /// Notes that no other note links to, regardless of their own links.
+    pub fn unreferenced(&self) -> Vec<&Note> {
+        self.index().unreferenced()
+    }
+
+    /// Names shared by more than one note, compared case-insensitively, with
+    /// the paths of every note using them. Sorted by name, then path.
+    pub fn duplicate_names(&self) -> Vec<(String, Vec<PathBuf>)> {
+        self.notes
+            .iter()
+            .map(|n| (nfc(&n.name()).to_lowercase(), n))
+            .into_group_map()
+            .into_values()
+            .filter(|notes| notes.len() > 1)
+            .map(|notes| {
+                let paths = notes
+                    .iter()
+                    .map(|n| n.path().to_path_buf())
+                    .sorted()
+                    .collect::<Vec<PathBuf>>();
+                let name = notes.iter().find(|n| n.path() == paths[0]).unwrap().name();
+
+                (name, paths)
+            })
+            .sorted_by(|(a, _), (b, _)| a.to_lowercase().cmp(&b.to_lowercase()).then(a.cmp(b)))
+            .collect()
+    }
+
+    /// Every alias, mapped to the name of the note it belongs to. An alias
+    /// several notes claim maps to the one links to it resolve to, unless
+    /// the alias is also a note's name; see `alias_collisions`.
+    pub fn alias_map(&self) -> HashMap<String, String> {
+        let resolver = self.resolver();
+
+        self.notes
+            .iter()
+            .flat_map(|n| n.aliases())
+            .filter_map(|alias| {
+                let note = resolver.alias_owners(&alias).first()?;
+                Some((alias, note.name()))
+            })
+            .collect()
+    }
+
+    /// Aliases claimed by more than one note, compared the way links are
+    /// resolved, with the paths of every note claiming them. Sorted by
+    /// alias, then path.
+    pub fn alias_collisions(&self) -> Vec<(String, Vec<PathBuf>)> {
+        self.notes
+            .iter()
+            .flat_map(|n| n.aliases().into_iter().map(move |alias| (alias, n)))
+            .into_group_map_by(|(alias, _)| normalize(alias))
+            .into_values()
+            .filter_map(|claims| {
+                let paths = claims
+                    .iter()
+                    .map(|(_, n)| n.path().to_path_buf())
+                    .sorted()
+                    .dedup()
+                    .collect::<Vec<PathBuf>>();
+                let alias = claims
+                    .iter()
+                    .find(|(_, n)| n.path() == paths[0])
+                    .map(|(alias, _)| alias.clone())?;
+
+                match paths.len() > 1 {
+                    true => Some((alias, paths)),
+                    false => None,
+                }
+            })
+            .sorted_by(|(a, _), (b, _)| a.to_lowercase().cmp(&b.to_lowercase()).then(a.cmp(b)))
+            .collect()
+    }
+
+    pub fn stats(&self) -> Stats {
+        self.index().stats()
+    }
+
+    /// `stats`, for the notes in each folder directly under the vault root,
+    /// with the notes right at the root as a folder of their own named "".
+    /// Sorted by note count, most first, then by folder.
+    pub fn folder_stats(&self) -> Vec<FolderStats> {
+        self.index().folder_stats()
+    }
+
+    fn resolver(&self) -> Resolver<'_> {
+        Resolver::new(&self.roots, &self.notes)
+    }
+}
+
+/// The one of `roots` that `path` lies under, if any. When roots are nested
+/// the deepest one wins, so a note always belongs to the vault closest to it.
+pub(crate) fn root_of<'a>(roots: &'a [PathBuf], path: &Path) -> Option<&'a Path> {
+    roots
+        .iter()
+        .filter(|root| path.starts_with(root))
+        .max_by_key(|root| root.components().count())
+        .map(|root| root.as_path())
+}
+
+/// The root of `i`'s set in a union-find forest, flattening the path to it
+/// along the way.
+fn find_root(parents: &mut [usize], mut i: usize) -> usize {
+    while parents[i] != i {
+        parents[i] = parents[parents[i]];
+        i = parents[i];
+    }
+
+    i
+}
+
+/// Loads the notes under one root, using and then updating its cache.
+fn scan(
+    root: &Path,
+    options: &ScanOptions,
+    query_cache: &Arc<QueryCache>,
+    timings: &mut Timings,
+) -> Result<(Vec<Note>, Vec<DarkstoneError>), DarkstoneError> {
+    // Otherwise a mistyped path is just an empty vault.
+    match std::fs::metadata(root) {
+        // A path through a file, like `Note.md/x`, can't exist either.
+        Err(err)
+            if matches!(
+                err.kind(),
+                std::io::ErrorKind::NotFound | std::io::ErrorKind::NotADirectory
+            ) =>
+        {
+            return Err(DarkstoneError::NoSuchVault(root.to_path_buf()))
+        }
+        Ok(metadata) if !metadata.is_dir() => {
+            return Err(DarkstoneError::NotADirectory(root.to_path_buf()))
+        }
+        _ => {}
+    }
+    if let Err(err) = std::fs::read_dir(root) {
+        return Err(DarkstoneError::Io(root.to_path_buf(), err));
+    }
+
+    let cache = match &options.cache_dir {
+        Some(dir) => Cache::load(dir, root, options, query_cache.fingerprint),
+        None => Cache::default(),
+    };
+    let started = Instant::now();
+    let entries = options
+        .walk(root)
+        .filter(|entry| match entry {
+            Ok(entry) if !entry.file_type().is_some_and(|t| t.is_file()) => false,
+            Ok(entry) if !options.has_extension(entry.path()) => {
+                debug!(path = %entry.path().display(), "skipping, not a note extension");
+                false
+            }
+            Ok(entry) if !options.is_in_range(entry.path()) => {
+                debug!(path = %entry.path().display(), "skipping, outside the date range");
+                false
+            }
+            _ => true,
+        })
+        .collect::<Vec<_>>();
+    timings.walk += started.elapsed();
+
+    let started = Instant::now();
+    let loaded = entries
+        .into_par_iter()
+        .inspect(|_| options.progress.inc(1))
+        .map(|entry| match entry {
+            Ok(entry) => {
+                let path = entry.into_path();
+                match cache.get(root, &path) {
+                    Some(summary) => {
+                        trace!(path = %path.display(), "using cached note");
+                        Ok(Note::from_summary(path, summary, query_cache.clone()))
+                    }
+                    None => Note::load(path, query_cache.clone(), options.lossy),
+                }
+            }
+            Err(err) => Err(DarkstoneError::Walk(err)),
+        })
+        .collect::<Vec<Result<Note, DarkstoneError>>>();
+    timings.parse += started.elapsed();
+
+    let started = Instant::now();
+    let (notes, errors): (Vec<Note>, Vec<DarkstoneError>) = loaded
+        .into_par_iter()
+        .map(|note| note.map(|n| options.prepared(n)))
+        .collect::<Vec<Result<Note, DarkstoneError>>>()
+        .into_iter()
+        .partition_result();
+    timings.extract += started.elapsed();
+
+    info!(
+        root = %root.display(),
+        notes = notes.len(),
+        errors = errors.len(),
+        "scanned vault"
+    );
+
+    // A cache that can't be written only costs speed next time.
+    if let Some(dir) = &options.cache_dir {
+        if let Err(err) = Cache::save(dir, root, options, query_cache.fingerprint, &notes) {
+            debug!(dir = %dir.display(), %err, "couldn't save the cache");
+        }
+    }
+
+    Ok((notes, errors))
+}
@@ -0,0 +1,246 @@
+//! The command line against the fixture vault, with each command's output
+//! compared to a file in `tests/golden`. Run with `UPDATE_GOLDEN=1` to
+//! rewrite those files after an intended change, then review the diff.
+//! Generated vaults check properties of the output instead.
+
+mod support;
+
+use assert_cmd::Command;
+use std::path::{Path, PathBuf};
+
+/// Runs darkstone on the fixture vault with `args`, checking it exits with
+/// `code` and prints what `tests/golden/<name>.txt` holds, once absolute
+/// paths are made relative to the crate again.
+fn golden(name: &str, args: &[&str], code: i32) {
+    let output = Command::cargo_bin("darkstone")
+        .unwrap()
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        // Keeps any queries in the user's config folder out of it.
+        .env("XDG_CONFIG_HOME", env!("CARGO_TARGET_TMPDIR"))
+        .args([
+            "--no-cache",
+            "--color",
+            "never",
+            "-v",
+            "tests/fixtures/vault",
+        ])
+        .args(args)
+        .assert()
+        .code(code)
+        .get_output()
+        .stdout
+        .clone();
+    let crate_dir = std::fs::canonicalize(env!("CARGO_MANIFEST_DIR")).unwrap();
+    let output = String::from_utf8(output)
+        .unwrap()
+        .replace(&format!("{}/", crate_dir.display()), "");
+
+    let file = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/golden")
+        .join(format!("{}.txt", name));
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        std::fs::write(&file, &output).unwrap();
+        return;
+    }
+    let expected = std::fs::read_to_string(&file).unwrap_or_default();
+
+    assert_eq!(
+        output,
+        expected,
+        "output of {:?} differs from {}",
+        args,
+        file.display()
+    );
+}
+
+#[test]
+fn targets() {
+    golden("targets", &[], 0);
+}
+
+#[test]
+fn targets_json() {
+    golden("targets-json", &["--format", "json"], 0);
+}
+
+#[test]
+fn targets_keep_extension() {
+    golden(
+        "targets-keep-extension",
+        &["--keep-extension", "--sort", "byte"],
+        0,
+    );
+}
+
+#[test]
+fn lint() {
+    golden("lint", &["lint"], 1);
+}
+
+#[test]
+fn lint_suggest() {
+    golden("lint-suggest", &["lint", "--suggest", "--self-links"], 1);
+}
+
+#[test]
+fn check() {
+    golden("check", &["check"], 1);
+}
+
+#[test]
+fn check_allowing_everything_passes() {
+    golden(
+        "check-allow",
+        &[
+            "check",
+            "--allow",
+            "broken-links,broken-anchors,missing-embeds,duplicates",
+        ],
+        0,
+    );
+}
+
+#[test]
+fn links() {
+    golden("links", &["links"], 0);
+}
+
+#[test]
+fn backlinks() {
+    golden("backlinks", &["backlinks", "Proj"], 0);
+}
+
+#[test]
+fn tags() {
+    golden("tags", &["tags"], 0);
+}
+
+#[test]
+fn tags_take_the_global_format() {
+    golden("tags-jsonl", &["tags", "--format", "jsonl"], 0);
+    golden("tags-jsonl", &["--format", "jsonl", "tags"], 0);
+}
+
+#[test]
+fn aliases() {
+    golden("aliases", &["aliases"], 0);
+}
+
+#[test]
+fn popular() {
+    golden("popular", &["popular"], 0);
+}
+
+#[test]
+fn duplicates() {
+    golden("duplicates", &["duplicates"], 0);
+}
+
+#[test]
+fn stats() {
+    golden("stats", &["stats"], 0);
+}
+
+#[test]
+fn graph() {
+    golden("graph", &["graph"], 0);
+}
+
+#[test]
+fn graph_with_broken_links() {
+    golden("graph-broken", &["graph", "--include-broken-as-nodes"], 0);
+}
+
+#[test]
+fn graph_d3_json() {
+    golden(
+        "graph-d3",
+        &["graph", "--format", "d3-json", "--include-broken-as-nodes"],
+        0,
+    );
+}
+
+#[test]
+fn link_events() {
+    golden("events", &["--format", "ndjson"], 0);
+}
+
+#[test]
+fn two_runs_print_the_same() {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("deterministic-vault");
+    let root = support::generate(&dir, 100).unwrap();
+    let run = |args: &[&str]| {
+        Command::cargo_bin("darkstone")
+            .unwrap()
+            .env("XDG_CONFIG_HOME", env!("CARGO_TARGET_TMPDIR"))
+            .args(["--no-cache", "--color", "never", "-v"])
+            .arg(&root)
+            .args(args)
+            .assert()
+            .get_output()
+            .stdout
+            .clone()
+    };
+
+    for args in [
+        &[][..],
+        &["--format", "json"],
+        &["aliases"],
+        &["links"],
+        &["lint"],
+        &["graph"],
+    ] {
+        let first = run(args);
+        assert!(!first.is_empty(), "{:?} printed nothing", args);
+        assert_eq!(first, run(args), "{:?} printed something else", args);
+    }
+}
+
+#[test]
+fn verbose_short_flag_stacks() {
+    let output = Command::cargo_bin("darkstone")
+        .unwrap()
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .args(["--no-cache", "-VV", "-v", "tests/fixtures/vault", "stats"])
+        .assert()
+        .success()
+        .get_output()
+        .stderr
+        .clone();
+
+    assert!(String::from_utf8(output).unwrap().contains("parsed note"));
+}
+
+#[test]
+fn rename_dry_run_changes_nothing() {
+    let root = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("rename-dry-run");
+    if root.exists() {
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+    std::fs::create_dir_all(&root).unwrap();
+    std::fs::write(root.join("Old.md"), "").unwrap();
+    std::fs::write(root.join("A.md"), "[[Old]] and [o][ref]\n\n[ref]: Old.md\n").unwrap();
+
+    let output = Command::cargo_bin("darkstone")
+        .unwrap()
+        .env("XDG_CONFIG_HOME", env!("CARGO_TARGET_TMPDIR"))
+        .args(["--no-cache", "--color", "never", "-v"])
+        .arg(&root)
+        .args(["rename", "Old", "New", "--dry-run"])
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+
+    assert_eq!(
+        String::from_utf8(output).unwrap(),
+        "A.md:1: Old -> New\nA.md:3: Old.md -> New.md\nOld.md -> New.md\n"
+    );
+    assert!(root.join("Old.md").exists());
+    assert!(!root.join("New.md").exists());
+    assert_eq!(
+        std::fs::read_to_string(root.join("A.md")).unwrap(),
+        "[[Old]] and [o][ref]\n\n[ref]: Old.md\n"
+    );
+}
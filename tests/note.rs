@@ -0,0 +1,337 @@
+//! Extraction from single notes parsed with `Note::from_str`, so no file
+//! is needed.
+
+use darkstone::{LinkKind, Note, QueryCache, Vault};
+use std::sync::Arc;
+
+fn parse(content: &str) -> Note {
+    Note::from_str("Note", content, Arc::new(QueryCache::new().unwrap())).unwrap()
+}
+
+#[test]
+fn name_and_path_come_from_the_name() {
+    let note = Note::from_str("folder/Note", "", Arc::new(QueryCache::new().unwrap())).unwrap();
+
+    assert_eq!(note.name(), "Note");
+    assert_eq!(note.path().to_str(), Some("folder/Note.md"));
+}
+
+#[test]
+fn aliases_as_a_scalar_flow_or_block_sequence() {
+    assert_eq!(parse("---\naliases: One\n---\n").aliases(), ["One"]);
+    assert_eq!(
+        parse("---\naliases: [One, 'Two']\n---\n").aliases(),
+        ["One", "Two"]
+    );
+    assert_eq!(
+        parse("---\naliases:\n  - One\n  - \"Two\"\n---\n").aliases(),
+        ["One", "Two"]
+    );
+    assert!(parse("# No frontmatter\n").aliases().is_empty());
+}
+
+#[test]
+fn quoted_aliases_in_every_yaml_syntax() {
+    assert_eq!(parse("---\naliases: \"A B\"\n---\n").aliases(), ["A B"]);
+    assert_eq!(parse("---\naliases: 'A B'\n---\n").aliases(), ["A B"]);
+
+    let flow = parse("---\naliases: [\"A B\", 'C: D', E]\n---\n");
+    let block = parse("---\naliases:\n  - \"A B\"\n  - 'C: D'\n  - E\n---\n");
+    assert_eq!(flow.aliases(), ["A B", "C: D", "E"]);
+    assert_eq!(block.aliases(), flow.aliases());
+    assert_eq!(block.targets(), flow.targets());
+}
+
+#[test]
+fn aliases_from_toml_and_json_frontmatter() {
+    assert_eq!(parse("+++\naliases = [\"One\"]\n+++\n").aliases(), ["One"]);
+    assert_eq!(parse("{\"aliases\": [\"One\"]}\n").aliases(), ["One"]);
+}
+
+#[test]
+fn every_frontmatter_format_reads_the_same() {
+    let body = "\n# Note\n\n[[Link]] #body\n";
+    let yaml = parse(&format!(
+        "---\naliases: [One, Two]\ntags: [a]\n---\n{}",
+        body
+    ));
+    let toml = parse(&format!(
+        "+++\naliases = [\"One\", \"Two\"]\ntags = [\"a\"]\n+++\n{}",
+        body
+    ));
+    let json = parse(&format!(
+        "{{\n  \"aliases\": [\"One\", \"Two\"],\n  \"tags\": [\"a\"]\n}}\n{}",
+        body
+    ));
+
+    assert_eq!(yaml.aliases(), ["One", "Two"]);
+    assert_eq!(yaml.tags(), ["a", "body"]);
+    for note in [&toml, &json] {
+        assert_eq!(note.aliases(), yaml.aliases());
+        assert_eq!(note.tags(), yaml.tags());
+        assert_eq!(note.targets(), yaml.targets());
+    }
+}
+
+#[test]
+fn crlf_and_byte_order_mark_read_like_lf() {
+    let lf = "---\naliases: [Win]\nup: \"[[Parent]]\"\n---\n\n# Note\n\nSee [[One]]\nand [[Two]]. #tag\n";
+    let crlf = lf.replace('\n', "\r\n");
+    // Only some lines ended with CRLF, as after editing on another system.
+    let mixed = lf.replacen('\n', "\r\n", 4);
+    let summary = |note: Note| {
+        let links = note
+            .links()
+            .into_iter()
+            .map(|l| (l.destination, l.start.line, l.start.column))
+            .collect::<Vec<_>>();
+        (note.aliases(), note.tags(), links)
+    };
+
+    let expected = summary(parse(lf));
+    assert_eq!(expected.0, ["Win"]);
+    assert_eq!(expected.2.len(), 3);
+    for content in [
+        format!("\u{feff}{}", lf),
+        format!("\u{feff}{}", crlf),
+        crlf,
+        mixed,
+    ] {
+        assert_eq!(summary(parse(&content)), expected, "{:?}", content);
+    }
+}
+
+#[test]
+fn tags_from_frontmatter_and_body() {
+    let note = parse("---\ntags: [a, b]\n---\n\nText #c and #nested/tag, not #1.\n");
+
+    assert_eq!(note.tags(), ["a", "b", "c", "nested/tag"]);
+}
+
+#[test]
+fn only_top_level_frontmatter_keys_count() {
+    let note = parse("---\nmeta:\n  tags: [inner]\n  aliases: [Nested]\ntags: [outer]\n---\n");
+
+    assert_eq!(note.tags(), ["outer"]);
+    assert!(note.aliases().is_empty());
+}
+
+#[test]
+fn frontmatter_links_come_first() {
+    let note = parse("---\nup: \"[[Parent]]\"\n---\n\n[[Child]]\n");
+
+    let destinations = note
+        .links()
+        .into_iter()
+        .map(|l| l.destination)
+        .collect::<Vec<String>>();
+    assert_eq!(destinations, ["Parent", "Child"]);
+}
+
+#[test]
+fn quoted_frontmatter_links_point_into_the_file() {
+    // The blank lines put the frontmatter somewhere other than byte 0.
+    let content = "\n\n---\nup: \"[[Parent]]\"\n---\n";
+    let links = parse(content).links();
+
+    assert_eq!(links.len(), 1);
+    assert_eq!(links[0].destination, "Parent");
+    assert_eq!(links[0].raw, "[[Parent]]");
+    assert_eq!(links[0].start.line, 3);
+    assert_eq!(links[0].start.column, 5);
+    let range = links[0].destination_range.clone().unwrap();
+    assert_eq!(&content[range], "Parent");
+}
+
+#[test]
+fn wiki_link_parts() {
+    let mut links = parse("[[Note#Heading|text]] ![[Image.png]] [[Other#^block]]\n").links();
+    links.sort_by_key(|l| l.start);
+
+    assert_eq!(links.len(), 3);
+    assert_eq!(links[0].destination, "Note");
+    assert_eq!(links[0].heading.as_deref(), Some("Heading"));
+    assert_eq!(links[0].text, "text");
+    assert!(links[1].is_embed);
+    assert_eq!(links[2].block.as_deref(), Some("block"));
+}
+
+#[test]
+fn inline_reference_and_autolinks() {
+    let content = "[a](<My Note.md>) [b][ref] <https://example.com>\n\n[ref]: Other.md\n";
+    let kinds = parse(content)
+        .links()
+        .into_iter()
+        .map(|l| (l.kind, l.destination))
+        .collect::<Vec<_>>();
+
+    assert_eq!(
+        kinds,
+        [
+            (LinkKind::Inline, "My Note.md".to_string()),
+            (LinkKind::Reference, "Other.md".to_string()),
+            (LinkKind::Autolink, "https://example.com".to_string()),
+        ]
+    );
+}
+
+#[test]
+fn links_in_code_are_left_out() {
+    let content = concat!(
+        "```sh\necho [[Fenced]] [a](Fenced.md)\n```\n\n",
+        "    [[Indented]]\n\n",
+        "Text `[[Span]]` and [[Real]].\n",
+    );
+    let destinations = parse(content)
+        .links()
+        .into_iter()
+        .map(|l| l.destination)
+        .collect::<Vec<String>>();
+
+    assert_eq!(destinations, ["Real"]);
+}
+
+#[test]
+fn commented_links_only_when_asked_for() {
+    let content = "<!-- [[Hidden]] -->\n\n%% [[Also Hidden]] %%\n";
+
+    assert!(parse(content).links().is_empty());
+    assert_eq!(parse(content).with_comments(true).links().len(), 2);
+}
+
+#[test]
+fn comments_hide_links_inline_and_across_lines() {
+    let content = concat!(
+        "Before %% [[Inline]] %% [[After]] <!-- [[Html]] -->\n\n",
+        "<!--\n[[Html Block]]\n-->\n\n",
+        "%%\n[[Block]]\n%%\n\n",
+        "[[Last]]\n",
+    );
+    let destinations = |note: Note| {
+        let mut links = note.links();
+        links.sort_by_key(|l| l.start);
+        links
+            .into_iter()
+            .map(|l| l.destination)
+            .collect::<Vec<String>>()
+    };
+
+    assert_eq!(destinations(parse(content)), ["After", "Last"]);
+    assert_eq!(
+        destinations(parse(content).with_comments(true)),
+        ["Inline", "After", "Html", "Html Block", "Block", "Last"]
+    );
+}
+
+#[test]
+fn escaped_brackets_are_not_links() {
+    for escaped in [
+        "Write \\[\\[Foo\\]\\] for a link.\n",
+        "Write \\[\\[Foo]] for a link.\n",
+        "Write \\[text](Foo.md) for a link.\n",
+    ] {
+        assert!(parse(escaped).links().is_empty(), "{:?}", escaped);
+    }
+
+    for unescaped in [
+        "Write [[Foo]] for a link.\n",
+        "Write [text](Foo.md) for a link.\n",
+    ] {
+        let links = parse(unescaped).links();
+        assert_eq!(links.len(), 1, "{:?}", unescaped);
+        assert_eq!(links[0].destination.trim_end_matches(".md"), "Foo");
+    }
+}
+
+#[test]
+fn angle_bracket_destinations_keep_their_spaces() {
+    let content = "[a](<Folder/My Note.md#Some Heading>) ![b](<My Image.png>)\n";
+    let mut links = parse(content).links();
+    links.sort_by_key(|l| l.start);
+
+    assert_eq!(links[0].destination, "Folder/My Note.md");
+    assert_eq!(links[0].heading.as_deref(), Some("Some Heading"));
+    let range = links[0].destination_range.clone().unwrap();
+    assert_eq!(&content[range], "Folder/My Note.md");
+    assert_eq!(links[1].destination, "My Image.png");
+    assert!(links[1].is_embed);
+}
+
+#[test]
+fn positions_count_lines_and_columns() {
+    let content = "[[First]]\n\nThen  [[Second]]\n\n\nLast: [[Third]]";
+    let mut links = parse(content).links();
+    links.sort_by_key(|l| l.start);
+    let positions = links
+        .iter()
+        .map(|l| (l.start.line, l.start.column, l.end.line, l.end.column))
+        .collect::<Vec<_>>();
+
+    assert_eq!(positions, [(0, 0, 0, 9), (2, 6, 2, 16), (5, 6, 5, 15)]);
+    assert_eq!(
+        &content[links[2].start.byte..links[2].end.byte],
+        "[[Third]]"
+    );
+}
+
+#[test]
+fn destination_range_covers_just_the_destination() {
+    let content = "See [[Folder/Note#Heading|text]].\n";
+    let link = &parse(content).links()[0];
+
+    let range = link.destination_range.clone().unwrap();
+    assert_eq!(&content[range], "Folder/Note");
+}
+
+#[test]
+fn raw_keeps_the_link_as_written() {
+    let mut links = parse("[[Note]] [[Note|Note]] ![[Image.png]] [a](<My Note.md>)\n").links();
+    links.sort_by_key(|l| l.start);
+    let raws = links.into_iter().map(|l| l.raw).collect::<Vec<String>>();
+
+    assert_eq!(
+        raws,
+        [
+            "[[Note]]",
+            "[[Note|Note]]",
+            "![[Image.png]]",
+            "[a](<My Note.md>)"
+        ]
+    );
+}
+
+#[test]
+fn schemes_are_external_in_any_case() {
+    let content = concat!(
+        "[a](HTTPS://example.com) [b](MAILTO:q@w) [c](obsidian://open) ",
+        "<https://example.org> [d](<Note: Draft.md>) [[Re: Plans]]\n",
+    );
+    let mut links = parse(content).links();
+    links.sort_by_key(|l| l.start);
+    let kinds = links
+        .iter()
+        .map(|l| (l.is_external(), l.is_url()))
+        .collect::<Vec<_>>();
+
+    assert_eq!(
+        kinds,
+        [
+            (true, true),
+            (true, false),
+            (true, false),
+            (true, true),
+            (false, false),
+            (false, false),
+        ]
+    );
+
+    let vault = Vault::from_notes(vec![parse(content)]).unwrap();
+    let broken = vault
+        .broken_links()
+        .into_iter()
+        .map(|l| l.destination)
+        .collect::<Vec<String>>();
+    assert_eq!(broken, ["Note: Draft.md", "Re: Plans"]);
+    assert_eq!(vault.stats().external_links, 4);
+    assert_eq!(vault.external_links().len(), 2);
+}
@@ -0,0 +1,71 @@
+//! Synthetic vaults, so benchmarks and tests run against the same notes on
+//! every machine.
+
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+
+/// Writes a vault of `notes` notes under `dir`, replacing any vault written
+/// there before. Notes are spread over a few folders and each links to a
+/// handful of others, chosen by a fixed formula so every run writes the same
+/// files. Roughly one link in twenty is broken.
+pub fn generate(dir: &Path, notes: usize) -> std::io::Result<PathBuf> {
+    if dir.exists() {
+        std::fs::remove_dir_all(dir)?;
+    }
+
+    for i in 0..notes {
+        let folder = dir.join(format!("folder {}", i % 10));
+        std::fs::create_dir_all(&folder)?;
+        std::fs::write(folder.join(format!("{}.md", name(i))), content(i, notes))?;
+    }
+
+    Ok(dir.to_path_buf())
+}
+
+fn name(i: usize) -> String {
+    format!("Note {:05}", i)
+}
+
+fn content(i: usize, notes: usize) -> String {
+    let mut content = String::new();
+    let _ = writeln!(content, "---");
+    let _ = writeln!(content, "aliases: [N{}]", i);
+    let _ = writeln!(content, "tags: [generated, group/{}]", i % 7);
+    let _ = writeln!(content, "---");
+    let _ = writeln!(content);
+    let _ = writeln!(content, "# {}", name(i));
+
+    for section in 0..3 {
+        let _ = writeln!(content);
+        let _ = writeln!(content, "## Section {}", section);
+        let _ = writeln!(content);
+
+        let linked = (i * 31 + section * 17 + 7) % notes.max(1);
+        let _ = writeln!(
+            content,
+            "Some text about [[{}]] and [[{}#Section {}|its section]], tagged #topic/{}.",
+            name(linked),
+            name((linked + 1) % notes.max(1)),
+            section,
+            (i + section) % 13,
+        );
+        let _ = writeln!(
+            content,
+            "See [the other one]({}.md) or <https://example.com/{}>.",
+            name((linked + 2) % notes.max(1)).replace(' ', "%20"),
+            i,
+        );
+        if (i + section).is_multiple_of(20) {
+            let _ = writeln!(content, "This one is [[Missing {}]].", i);
+        }
+
+        let _ = writeln!(content);
+        let _ = writeln!(content, "- [ ] A task for [[{}]]", name(linked));
+        let _ = writeln!(content);
+        let _ = writeln!(content, "```rust");
+        let _ = writeln!(content, "let link = \"[[Not a link]]\";");
+        let _ = writeln!(content, "```");
+    }
+
+    content
+}
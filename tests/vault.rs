@@ -0,0 +1,839 @@
+//! The library against the fixture vault in `tests/fixtures/vault`, and
+//! against generated vaults where only the shape of the result matters.
+
+mod support;
+
+use darkstone::{DarkstoneError, LinkKind, QueryCache, Rename, ScanOptions, Target, Vault};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+fn fixture() -> Vault {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/vault");
+
+    Vault::new(root).unwrap()
+}
+
+/// `path` relative to the fixture vault, with `/` between folders.
+fn relative(vault: &Vault, path: &Path) -> String {
+    vault
+        .relative_path(path)
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+fn note(name: &str) -> Target {
+    Target::Note(name.to_string())
+}
+
+fn alias(note: &str, alias: &str) -> Target {
+    Target::Alias {
+        note: note.to_string(),
+        alias: alias.to_string(),
+    }
+}
+
+#[test]
+fn loads_every_note_but_templates() {
+    let vault = fixture();
+
+    let mut notes = vault
+        .notes()
+        .iter()
+        .map(|n| relative(&vault, n.path()))
+        .collect::<Vec<String>>();
+    notes.sort();
+
+    assert!(vault.errors().is_empty());
+    assert_eq!(
+        notes,
+        [
+            "Comments.md",
+            "Json.md",
+            "Links.md",
+            "Meeting Notes.md",
+            "Project.md",
+            "Roadmap.md",
+            "Toml.md",
+            "Windows.md",
+            "a/Same.md",
+            "b/Same.md",
+        ]
+    );
+    assert_eq!(vault.templates().len(), 1);
+}
+
+#[test]
+fn aliases_in_every_frontmatter_syntax() {
+    let vault = fixture();
+    let aliases = |name: &str| vault.resolve(name).unwrap().aliases();
+
+    assert_eq!(aliases("Project"), ["Proj", "The Project"]);
+    assert_eq!(aliases("Roadmap"), ["Plan", "Long-term plan"]);
+    assert_eq!(aliases("Meeting Notes"), ["Meetings"]);
+    assert_eq!(aliases("Toml"), ["Tom"]);
+    assert_eq!(aliases("Json"), ["Jay"]);
+    assert_eq!(aliases("Windows"), ["Win"]);
+}
+
+#[test]
+fn toml_and_json_frontmatter_read_like_yaml() {
+    let vault = fixture();
+
+    for name in ["Toml", "Json"] {
+        let note = vault.resolve(name).unwrap();
+        assert_eq!(note.tags(), ["config"], "{}", name);
+        assert!(note.links().iter().all(|l| vault.resolve_link(l).is_some()));
+    }
+    assert_eq!(
+        vault.resolve("Tom").map(|n| n.name()).as_deref(),
+        Some("Toml")
+    );
+    assert_eq!(
+        vault.resolve("Jay").map(|n| n.name()).as_deref(),
+        Some("Json")
+    );
+}
+
+#[test]
+fn targets_hold_names_aliases_and_destinations() {
+    let targets = fixture().targets();
+
+    for target in [
+        note("Project"),
+        alias("Project", "Proj"),
+        alias("Project", "The Project"),
+        alias("Roadmap", "Long-term plan"),
+        alias("Windows", "Win"),
+        note("Nowhere"),
+        note("diagram.png"),
+        note("https://example.com/roadmap"),
+    ] {
+        assert!(targets.contains(&target), "missing {}", target);
+    }
+
+    // Link destinations are reduced to the note name, and each target is
+    // only listed once.
+    assert!(!targets.contains(&note("Plan.md")));
+    assert_eq!(targets.iter().filter(|&t| *t == note("Same")).count(), 1);
+    assert!(!targets.contains(&note("Not a link")));
+}
+
+#[test]
+fn raw_targets_keep_destinations_as_written() {
+    let targets = fixture().raw_targets();
+
+    assert!(targets.contains(&note("Plan.md")));
+    assert!(targets.contains(&note("Meeting Notes.md")));
+}
+
+#[test]
+fn links_of_every_kind() {
+    let vault = fixture();
+    let links = vault.resolve("Project").unwrap().links();
+
+    let destinations = links
+        .iter()
+        .map(|l| (l.kind, l.raw_destination.as_str(), l.is_embed))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        destinations,
+        [
+            (LinkKind::Wiki, "Meeting Notes", false),
+            (LinkKind::Wiki, "Roadmap", false),
+            (LinkKind::Wiki, "Roadmap#Q3", false),
+            (LinkKind::Wiki, "Roadmap#Q5", false),
+            (LinkKind::Wiki, "diagram.png", true),
+            (LinkKind::Wiki, "Roadmap", true),
+        ]
+    );
+    assert_eq!(links[2].text, "the third quarter");
+    assert_eq!(links[2].heading.as_deref(), Some("Q3"));
+}
+
+#[test]
+fn links_in_comments_and_code_are_left_out() {
+    let vault = fixture();
+
+    let destinations = vault
+        .links()
+        .into_iter()
+        .map(|l| l.destination)
+        .collect::<Vec<String>>();
+
+    for hidden in ["Hidden", "Also Hidden", "Not a link"] {
+        assert!(!destinations.contains(&hidden.to_string()), "{}", hidden);
+    }
+}
+
+#[test]
+fn include_comments_brings_commented_links_back() {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/vault");
+    let options = ScanOptions {
+        include_comments: true,
+        ..ScanOptions::default()
+    };
+    let vault = Vault::with_options(root, &options, Arc::new(QueryCache::new().unwrap())).unwrap();
+
+    let mut links = vault.resolve("Comments").unwrap().links();
+    links.sort_by_key(|l| l.start);
+    let destinations = links
+        .into_iter()
+        .map(|l| l.destination)
+        .collect::<Vec<String>>();
+    assert_eq!(destinations, ["Project", "Hidden", "Also Hidden"]);
+}
+
+#[test]
+fn every_way_of_writing_a_space_resolves() {
+    let vault = fixture();
+    let links = vault.resolve("Links").unwrap().links();
+
+    assert_eq!(links.len(), 4);
+    for link in &links {
+        let note = vault.resolve_link(link).map(|n| n.name());
+        assert_eq!(note.as_deref(), Some("Meeting Notes"), "{:?}", link);
+    }
+}
+
+#[test]
+fn angle_brackets_and_escapes_leave_the_raw_link_alone() {
+    let vault = fixture();
+    let mut links = vault.resolve("Links").unwrap().links();
+    links.sort_by_key(|l| l.start);
+
+    let angle = &links[1];
+    assert_eq!(angle.destination, "Meeting Notes.md");
+    assert_eq!(angle.raw, "[angle brackets](<Meeting Notes.md>)");
+    let escaped = &links[2];
+    assert_eq!(escaped.destination, "Meeting%20Notes.md");
+    assert_eq!(escaped.decoded_destination(), "Meeting Notes.md");
+    assert_eq!(escaped.raw, "[escaped](Meeting%20Notes.md)");
+    let reference = &links[3];
+    assert_eq!(reference.destination, "Meeting Notes.md");
+}
+
+#[test]
+fn resolves_names_aliases_and_paths() {
+    let vault = fixture();
+    let resolved = |destination: &str| {
+        vault
+            .resolve(destination)
+            .map(|n| relative(&vault, n.path()))
+    };
+
+    assert_eq!(resolved("Project").as_deref(), Some("Project.md"));
+    assert_eq!(resolved("project").as_deref(), Some("Project.md"));
+    assert_eq!(resolved("Project.md").as_deref(), Some("Project.md"));
+    assert_eq!(resolved("Proj").as_deref(), Some("Project.md"));
+    assert_eq!(resolved("Long-term plan").as_deref(), Some("Roadmap.md"));
+    assert_eq!(resolved("b/Same").as_deref(), Some("b/Same.md"));
+    assert_eq!(resolved("Nowhere"), None);
+}
+
+#[test]
+fn ambiguous_names_resolve_closest_to_the_root() {
+    let root = scratch_vault(
+        Path::new("ambiguous"),
+        &[
+            ("A.md", "[[note]]"),
+            ("deep/er/Note.md", ""),
+            ("deep/Note.md", ""),
+        ],
+    );
+    let vault = Vault::new(root).unwrap();
+
+    let candidates = vault
+        .resolve_all("Note")
+        .into_iter()
+        .map(|n| relative(&vault, n.path()))
+        .collect::<Vec<String>>();
+    assert_eq!(candidates, ["deep/Note.md", "deep/er/Note.md"]);
+    assert_eq!(
+        vault.resolve("Note").map(|n| relative(&vault, n.path())),
+        Some("deep/Note.md".to_string())
+    );
+    assert_eq!(vault.backlinks("deep/Note").len(), 1);
+    assert!(vault.backlinks("deep/er/Note").is_empty());
+    assert!(vault.broken_links().is_empty());
+}
+
+#[test]
+fn reports_broken_links_and_anchors() {
+    let vault = fixture();
+
+    let mut broken = vault
+        .broken_links()
+        .into_iter()
+        .map(|l| l.destination)
+        .collect::<Vec<String>>();
+    broken.sort();
+    // `![[diagram.png]]` doesn't resolve to a note, but the file exists.
+    assert_eq!(broken, ["Nowhere", "Projct"]);
+
+    let anchors = vault
+        .broken_anchors()
+        .into_iter()
+        .map(|l| l.raw_destination)
+        .collect::<Vec<String>>();
+    assert_eq!(anchors, ["Roadmap#Q5"]);
+}
+
+#[test]
+fn finds_duplicate_names() {
+    let vault = fixture();
+    let duplicates = vault.duplicate_names();
+
+    assert_eq!(duplicates.len(), 1);
+    assert_eq!(duplicates[0].0, "Same");
+    let paths = duplicates[0]
+        .1
+        .iter()
+        .map(|p| relative(&vault, p))
+        .collect::<Vec<String>>();
+    assert_eq!(paths, ["a/Same.md", "b/Same.md"]);
+}
+
+#[test]
+fn backlinks_come_through_aliases() {
+    let vault = fixture();
+
+    let mut names = vault
+        .backlinks("Project")
+        .iter()
+        .map(|n| relative(&vault, n.path()))
+        .collect::<Vec<String>>();
+    names.sort();
+
+    assert_eq!(
+        names,
+        [
+            "Comments.md",
+            "Meeting Notes.md",
+            "Roadmap.md",
+            "Windows.md",
+            "b/Same.md",
+        ]
+    );
+}
+
+#[test]
+fn byte_order_mark_and_crlf_leave_positions_alone() {
+    let vault = fixture();
+    let windows = vault.resolve("Windows").unwrap();
+
+    assert_eq!(windows.tags(), ["crlf"]);
+    let links = windows.links();
+    assert_eq!(links.len(), 2);
+    assert_eq!(links[0].destination, "Project");
+    assert_eq!(links[0].start.line, 6);
+    assert_eq!(links[0].start.column, 29);
+    assert_eq!(
+        vault.resolve_link(&links[1]).map(|n| n.name()).as_deref(),
+        Some("Roadmap")
+    );
+}
+
+#[test]
+fn generated_vault_breaks_the_links_it_means_to() {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("generated-vault");
+    let vault = Vault::new(support::generate(&dir, 100).unwrap()).unwrap();
+
+    assert_eq!(vault.notes().len(), 100);
+    assert!(vault
+        .broken_links()
+        .iter()
+        .all(|l| l.destination.starts_with("Missing ")));
+    // A section links to a missing note when `i + section` is a multiple
+    // of twenty: five of the hundred notes for each of the three sections.
+    assert_eq!(vault.broken_links().len(), 15);
+}
+
+/// Writes `files`, as `(relative path, content)` pairs, into a fresh folder
+/// `dir` under the test scratch directory.
+fn scratch_vault(dir: &Path, files: &[(&str, &str)]) -> PathBuf {
+    let root = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(dir);
+    if root.exists() {
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+    for (path, content) in files {
+        let path = root.join(path);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, content).unwrap();
+    }
+
+    root
+}
+
+#[test]
+fn a_vault_path_through_a_file_does_not_exist() {
+    let root = scratch_vault(Path::new("through-a-file"), &[("Note.md", "")]);
+
+    assert!(matches!(
+        Vault::new(root.join("Note.md/x")),
+        Err(DarkstoneError::NoSuchVault(_))
+    ));
+}
+
+#[test]
+fn glob_characters_in_the_vault_path_are_just_characters() {
+    let root = scratch_vault(
+        Path::new("[work] *?/vault"),
+        &[
+            ("A.md", "[[B]] and [[C]]"),
+            ("sub/B.md", "[[A]]"),
+            ("skip/C.md", "[[A]]"),
+        ],
+    );
+    let mut options = ScanOptions::default();
+    options.exclude_glob("skip").unwrap();
+    let vault = Vault::with_options(root, &options, Arc::new(QueryCache::new().unwrap())).unwrap();
+
+    let mut names = vault.notes().iter().map(|n| n.name()).collect::<Vec<_>>();
+    names.sort();
+    assert_eq!(names, ["A", "B"]);
+    assert_eq!(vault.backlinks("A").len(), 1);
+}
+
+#[cfg(unix)]
+#[test]
+fn vault_path_need_not_be_utf8() {
+    use std::ffi::OsStr;
+    use std::os::unix::ffi::OsStrExt;
+
+    let dir = Path::new(OsStr::from_bytes(b"not utf-8 \xff"));
+    let root = scratch_vault(dir, &[("A.md", "[[B]]"), ("B.md", "[[A]]")]);
+    let vault = Vault::new(root).unwrap();
+
+    assert!(vault.errors().is_empty());
+    assert_eq!(vault.notes().len(), 2);
+    assert!(vault.broken_links().is_empty());
+}
+
+#[test]
+fn broken_nodes_keep_clear_of_note_names() {
+    let root = scratch_vault(
+        Path::new("broken-nodes"),
+        &[("A.md", "[[Nowhere]]"), ("broken:Nowhere.md", "")],
+    );
+    let dot = darkstone::export::dot(&Vault::new(root).unwrap(), true);
+
+    assert!(dot.contains("    \"broken:Nowhere\";\n"));
+    assert!(dot.contains("    \"A\" -> \"broken:broken:Nowhere\" [style=dashed, color=red];\n"));
+}
+
+#[test]
+fn sqlite_tags_list_each_tag_once_per_note() {
+    let root = scratch_vault(
+        Path::new("sqlite-tags"),
+        &[("A.md", "---\ntags: [a]\n---\n#a #b #a\n")],
+    );
+    let vault = Vault::new(root.clone()).unwrap();
+    let path = root.join("vault.db");
+    darkstone::export::sqlite(&vault, &path).unwrap();
+
+    let connection = rusqlite::Connection::open(&path).unwrap();
+    let mut statement = connection
+        .prepare("SELECT note, tag FROM tags ORDER BY tag")
+        .unwrap();
+    let rows = statement
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+        .unwrap()
+        .collect::<Result<Vec<(String, String)>, _>>()
+        .unwrap();
+    assert_eq!(
+        rows,
+        [
+            ("A.md".to_string(), "a".to_string()),
+            ("A.md".to_string(), "b".to_string())
+        ]
+    );
+}
+
+#[test]
+fn graphml_is_well_formed_xml() {
+    let root = scratch_vault(
+        Path::new("graphml"),
+        &[
+            (
+                "R&D/<Plans> \"2024\".md",
+                "---\naliases: [Plans]\n---\n[[Tom's & Jerry's]] [[Nowhere & never]]",
+            ),
+            ("Tom's & Jerry's.md", "[[Plans]]"),
+        ],
+    );
+    let graphml = darkstone::export::graphml(&Vault::new(root).unwrap(), true);
+    let document = roxmltree::Document::parse(&graphml).unwrap();
+
+    let labels = document
+        .descendants()
+        .filter(|n| n.has_tag_name("data") && n.attribute("key") == Some("label"))
+        .map(|n| n.text().unwrap_or_default())
+        .collect::<Vec<&str>>();
+    assert_eq!(
+        labels,
+        ["<Plans> \"2024\"", "Tom's & Jerry's", "Nowhere & never"]
+    );
+    let edges = document
+        .descendants()
+        .filter(|n| n.has_tag_name("edge"))
+        .map(|n| {
+            (
+                n.attribute("source").unwrap(),
+                n.attribute("target").unwrap(),
+            )
+        })
+        .collect::<Vec<(&str, &str)>>();
+    assert_eq!(
+        edges,
+        [
+            ("R&D/<Plans> \"2024\".md", "Tom's & Jerry's.md"),
+            ("Tom's & Jerry's.md", "R&D/<Plans> \"2024\".md"),
+            ("R&D/<Plans> \"2024\".md", "broken:Nowhere & never"),
+        ]
+    );
+}
+
+#[test]
+fn composed_and_decomposed_accents_match() {
+    // "Café" with a decomposed é, as macOS writes file names, and a composed
+    // one, as most keyboards type it.
+    let decomposed = "Cafe\u{301}";
+    let composed = "Caf\u{e9}";
+    let root = scratch_vault(
+        Path::new("unicode"),
+        &[
+            (&format!("{}.md", decomposed), "[[Cr\u{e8}me]]"),
+            ("Cre\u{300}me.md", &format!("[[{}]]", composed)),
+        ],
+    );
+    let vault = Vault::new(root).unwrap();
+
+    assert!(vault.broken_links().is_empty());
+    assert_eq!(vault.backlinks(composed).len(), 1);
+    assert_eq!(vault.backlinks(decomposed).len(), 1);
+    let mut targets = vault.targets();
+    targets.sort();
+    assert_eq!(targets, [note(composed), note("Cr\u{e8}me")]);
+}
+
+#[test]
+fn inbound_counts_keep_same_named_notes_apart() {
+    let root = scratch_vault(
+        Path::new("inbound"),
+        &[
+            ("a/Same.md", ""),
+            ("b/Same.md", ""),
+            ("One.md", "[[a/Same]] [[b/Same]]"),
+            ("Two.md", "[[b/Same]]"),
+        ],
+    );
+    let vault = Vault::new(root).unwrap();
+
+    let mut counts = vault
+        .inbound_counts()
+        .into_iter()
+        .map(|(path, count)| (relative(&vault, &path), count))
+        .collect::<Vec<(String, usize)>>();
+    counts.sort();
+    assert_eq!(
+        counts,
+        [("a/Same.md".to_string(), 1), ("b/Same.md".to_string(), 2)]
+    );
+}
+
+#[test]
+fn names_and_paths_reach_the_same_note() {
+    let root = scratch_vault(
+        Path::new("paths"),
+        &[
+            ("Work/Projects/Roadmap.md", ""),
+            ("Roadmap.md", ""),
+            ("Home/Roadmap.md", ""),
+            (
+                "Work/Meetings/Standup.md",
+                "[[Work/Projects/Roadmap]] [up](../Projects/Roadmap.md)",
+            ),
+        ],
+    );
+    let vault = Vault::new(root).unwrap();
+    let resolved = |destination: &str| {
+        vault
+            .resolve(destination)
+            .map(|n| relative(&vault, n.path()))
+    };
+
+    // The basename finds the note closest to the root; anything with a
+    // folder in it finds that folder's note.
+    assert_eq!(resolved("Roadmap").as_deref(), Some("Roadmap.md"));
+    for destination in [
+        "Work/Projects/Roadmap",
+        "Work/Projects/Roadmap.md",
+        "work/projects/roadmap",
+        "/Work/Projects/Roadmap",
+        "Projects/Roadmap",
+    ] {
+        assert_eq!(
+            resolved(destination).as_deref(),
+            Some("Work/Projects/Roadmap.md"),
+            "{}",
+            destination
+        );
+    }
+    assert_eq!(resolved("Home/Roadmap").as_deref(), Some("Home/Roadmap.md"));
+    assert_eq!(resolved("Elsewhere/Roadmap"), None);
+
+    let standup = vault.resolve("Standup").unwrap();
+    for link in standup.links() {
+        let note = vault
+            .resolve_link(&link)
+            .map(|n| relative(&vault, n.path()));
+        assert_eq!(
+            note.as_deref(),
+            Some("Work/Projects/Roadmap.md"),
+            "{:?}",
+            link
+        );
+    }
+}
+
+#[test]
+fn nested_roots_resolve_the_same_in_any_order() {
+    let outer = scratch_vault(
+        Path::new("nested-roots"),
+        &[
+            ("Top.md", "[[Deep/Note]]"),
+            ("inner/Deep/Note.md", "[[Top]]"),
+        ],
+    );
+    let inner = outer.join("inner");
+    let load = |roots: Vec<PathBuf>| {
+        let options = ScanOptions::default();
+        Vault::with_roots(roots, &options, Arc::new(QueryCache::new().unwrap())).unwrap()
+    };
+    let resolved = |vault: &Vault, destination: &str| {
+        vault.resolve(destination).map(|n| n.path().to_path_buf())
+    };
+
+    let outer_first = load(vec![outer.clone(), inner.clone()]);
+    let inner_first = load(vec![inner, outer]);
+    for destination in ["Deep/Note", "inner/Deep/Note", "Top"] {
+        assert_eq!(
+            resolved(&outer_first, destination),
+            resolved(&inner_first, destination),
+            "{}",
+            destination
+        );
+    }
+    // The note belongs to the deeper root, as `relative_path` says.
+    assert!(resolved(&outer_first, "Deep/Note").is_some());
+    assert!(outer_first.broken_links().is_empty());
+}
+
+#[test]
+fn relative_paths_stay_inside_the_vault() {
+    let root = scratch_vault(
+        Path::new("relative"),
+        &[("A.md", "[a](./B.md) [b](../B.md)"), ("B.md", "")],
+    );
+    let vault = Vault::new(root).unwrap();
+
+    let broken = vault
+        .broken_links()
+        .into_iter()
+        .map(|l| l.destination)
+        .collect::<Vec<String>>();
+    assert_eq!(broken, ["../B.md"]);
+}
+
+#[test]
+fn looks_notes_up_by_name_alias_and_path() {
+    let vault = fixture();
+    let by_name = |name: &str| vault.note_by_name(name).map(|n| relative(&vault, n.path()));
+
+    assert_eq!(by_name("Project").as_deref(), Some("Project.md"));
+    assert_eq!(by_name("project.md").as_deref(), Some("Project.md"));
+    assert_eq!(by_name("The Project").as_deref(), Some("Project.md"));
+    assert_eq!(by_name("Same").as_deref(), Some("a/Same.md"));
+    assert_eq!(by_name("a/Same"), None);
+    assert_eq!(by_name("Nowhere"), None);
+
+    let roadmap = vault.note_by_name("Plan").unwrap();
+    let found = vault.note_by_path(roadmap.path()).map(|n| n.name());
+    assert_eq!(found.as_deref(), Some("Roadmap"));
+    assert!(vault.note_by_path(Path::new("Roadmap.md")).is_none());
+}
+
+#[test]
+fn lookups_follow_reloads() {
+    let root = scratch_vault(Path::new("lookup"), &[("A.md", "")]);
+    let mut vault = Vault::new(root.clone()).unwrap();
+    assert!(vault.note_by_name("B").is_none());
+
+    std::fs::write(root.join("B.md"), "").unwrap();
+    vault.reload(&root.join("B.md"));
+    assert!(vault.note_by_name("B").is_some());
+    assert!(vault.note_by_path(&root.join("B.md")).is_some());
+}
+
+#[test]
+fn reloads_skip_what_the_scan_skips() {
+    let root = scratch_vault(
+        Path::new("reload-ignore"),
+        &[("A.md", ""), (".gitignore", "drafts/\nSecret.md\n")],
+    );
+    let mut vault = Vault::new(root.clone()).unwrap();
+
+    for skipped in ["Secret.md", "drafts/B.md", ".hidden/C.md", ".obsidian/D.md"] {
+        let path = root.join(skipped);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(&path, "").unwrap();
+        assert!(!vault.reload(&path), "{}", skipped);
+    }
+    std::fs::write(root.join("E.md"), "").unwrap();
+    assert!(vault.reload(&root.join("E.md")));
+
+    let names = vault.notes().iter().map(|n| n.name()).collect::<Vec<_>>();
+    assert_eq!(names, ["A", "E"]);
+}
+
+#[test]
+fn raw_links_are_the_bytes_they_cover() {
+    let vault = fixture();
+
+    for link in vault.links() {
+        let file = std::fs::read_to_string(&link.source).unwrap();
+        let content = file.strip_prefix('\u{feff}').unwrap_or(&file);
+
+        assert_eq!(&content[link.byte_range()], link.raw, "{:?}", link);
+    }
+}
+
+#[test]
+fn max_depth_limits_how_far_down_notes_are_found() {
+    let root = scratch_vault(
+        Path::new("depth"),
+        &[("A.md", ""), ("one/B.md", ""), ("one/two/C.md", "")],
+    );
+    let names = |max_depth| {
+        let options = ScanOptions {
+            max_depth,
+            ..ScanOptions::default()
+        };
+        let query_cache = Arc::new(QueryCache::new().unwrap());
+        let mut vault = Vault::with_options(root.clone(), &options, query_cache).unwrap();
+        // Reloading a note below the limit doesn't sneak it in.
+        vault.reload(&root.join("one/two/C.md"));
+
+        vault.notes().iter().map(|n| n.name()).collect::<Vec<_>>()
+    };
+
+    assert_eq!(names(Some(1)), ["A"]);
+    assert_eq!(names(Some(2)), ["A", "B"]);
+    assert_eq!(names(None), ["A", "B", "C"]);
+}
+
+#[test]
+fn markdown_links_resolve_through_escapes_and_anchors() {
+    let root = scratch_vault(
+        Path::new("escapes"),
+        &[
+            ("My Note.md", "# My Note\n\n## Big Section\n"),
+            ("sub/Other File.md", ""),
+            (
+                "A.md",
+                concat!(
+                    "[a](My%20Note.md#Big%20Section) [b](sub/Other%20File.md) ",
+                    "[c](<My Note.md#Big Section>) [d](My%20Note.md#Nope) ",
+                    "[[My%20Note]]",
+                ),
+            ),
+        ],
+    );
+    let vault = Vault::new(root).unwrap();
+    let mut links = vault.note_by_name("A").unwrap().links();
+    links.sort_by_key(|l| l.start);
+
+    let resolved = links
+        .iter()
+        .map(|l| vault.resolve_link(l).map(|n| n.name()))
+        .collect::<Vec<_>>();
+    let names = [
+        Some("My Note"),
+        Some("Other File"),
+        Some("My Note"),
+        Some("My Note"),
+        None,
+    ];
+    assert_eq!(resolved, names.map(|n| n.map(String::from)));
+
+    // The link keeps what was written; only matching decodes it.
+    assert_eq!(links[0].raw_destination, "My%20Note.md#Big%20Section");
+    assert_eq!(links[0].destination, "My%20Note.md");
+    assert_eq!(links[0].decoded_destination(), "My Note.md");
+
+    let anchors = vault
+        .broken_anchors()
+        .into_iter()
+        .map(|l| l.raw_destination)
+        .collect::<Vec<String>>();
+    assert_eq!(anchors, ["My%20Note.md#Nope"]);
+
+    let targets = vault.note_by_name("A").unwrap().targets();
+    assert!(targets.contains(&note("My Note")));
+    assert!(targets.contains(&note("Other File")));
+    assert!(targets.contains(&note("My%20Note")));
+}
+
+#[test]
+fn rename_rewrites_every_link_but_aliases() {
+    let linking = concat!(
+        "\u{feff}[[Old Name|shown]] [[Old Name#Heading]] [i](Old%20Name.md)\n",
+        "[a](<Old Name.md>) [r][ref] [again][ref] [[Alias]]\n",
+        "\n",
+        "[ref]: Old%20Name.md\n",
+    );
+    let root = scratch_vault(
+        Path::new("rename"),
+        &[
+            ("Old Name.md", "---\naliases: [Alias]\n---\n## Heading\n"),
+            ("sub/A.md", linking),
+        ],
+    );
+    let vault = Vault::new(root.clone()).unwrap();
+
+    let rename = Rename::plan(&vault, "Old Name", "New Name").unwrap();
+    // One edit for the definition, however many links use it.
+    let edits = rename
+        .edits
+        .iter()
+        .map(|e| (e.line, e.original.as_str(), e.replacement.as_str()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        edits,
+        [
+            (0, "Old Name", "New Name"),
+            (0, "Old Name", "New Name"),
+            (0, "Old%20Name.md", "New%20Name.md"),
+            (1, "Old Name.md", "New Name.md"),
+            (3, "Old%20Name.md", "New%20Name.md"),
+        ]
+    );
+    rename.apply().unwrap();
+
+    assert!(!root.join("Old Name.md").exists());
+    assert!(root.join("New Name.md").exists());
+    assert_eq!(
+        std::fs::read_to_string(root.join("sub/A.md")).unwrap(),
+        concat!(
+            "\u{feff}[[New Name|shown]] [[New Name#Heading]] [i](New%20Name.md)\n",
+            "[a](<New Name.md>) [r][ref] [again][ref] [[Alias]]\n",
+            "\n",
+            "[ref]: New%20Name.md\n",
+        )
+    );
+    let vault = Vault::new(root).unwrap();
+    assert!(vault.broken_links().is_empty());
+    assert!(vault.broken_anchors().is_empty());
+}